@@ -0,0 +1,564 @@
+//! Command-line tool to inspect, dump and render module files.
+//!
+//! Built with the `cli` feature : `cargo run --features cli -- <subcommand> ...`
+
+extern crate openmpt;
+
+use openmpt::info;
+use openmpt::module::{self, Module, Logger, CouldOpenEffort};
+use openmpt::module::ctls::{Ctl, CtlType, CtlValue, DitherMode, AmigaFilterType, AtEndBehavior};
+use openmpt::module::render::ReadStatus;
+use openmpt::module::highlight::{HighlightStyle, PatternTextOptions};
+use openmpt::probe::{self, ProbeFlags};
+use std::collections::HashMap;
+use std::env;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write, BufWriter};
+use std::process;
+use std::str::FromStr;
+
+const USAGE:&str = "\
+Usage: openmpt-rs <subcommand> <file> [options]
+
+Subcommands:
+  info      Print the module metadata and contents
+  render    Render the module to a WAV or raw file
+  patterns  Dump the pattern data as text
+  probe     Print how likely libopenmpt is to open the file
+
+Render options:
+  -o, --output <path>            Output file (default: <file>.wav or <file>.raw)
+  --format <wav|raw>             Output format, 16 bit stereo (default: wav)
+  --rate <hz>                    Sample rate (default: 48000)
+  --subsong <n>                  Sub-song to render (default: the first one)
+  --repeat <n>                   Repeat count, -1 to repeat forever (requires --max-seconds)
+  --max-seconds <s>              Stop rendering after that many seconds
+  --gain <millibel>              Master gain
+  --stereo-separation <percent>  Stereo separation
+  --interpolation <taps>         Interpolation filter length
+  --volume-ramping <strength>    Volume ramping strength
+  --tempo-factor <factor>        Ctl play.tempo_factor
+  --pitch-factor <factor>        Ctl play.pitch_factor
+  --dither <0-3>                 Ctl dither
+  --at-end <fadeout|continue|stop>
+                                 Ctl play.at_end
+  --amiga                        Ctl render.resampler.emulate_amiga
+  --amiga-filter <auto|a500|a1200|unfiltered>
+                                 Ctl render.resampler.emulate_amiga_type
+  --ctl <key=value>              Any other ctl, can be repeated. load.* ctls
+                                 are passed to libopenmpt while loading
+
+Pattern options:
+  --pattern <n>                  Only dump that pattern
+  --order <n>                    Only dump the pattern at that order position
+  --width <n>                    Width of each cell (default: 13)
+  --channels <n,n,...>           Only dump these channels, in that order
+  --color <none|ansi|html>       Highlighting style (default: none)
+";
+
+// Largest number of frames that fit in a WAV file, whose sizes are 32 bit
+const MAX_WAV_FRAMES:usize = (u32::MAX as usize - 36) / 4;
+
+// Options that don't take a value
+const FLAGS:[&str; 3] = ["amiga", "help", "h"];
+
+fn main() {
+	let args: Vec<String> = env::args().skip(1).collect();
+
+	if let Err(message) = run(&args) {
+		eprintln!("openmpt-rs: {}", message);
+		process::exit(1);
+	}
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+	let args = Args::parse(args)?;
+
+	if args.has_flag("help") || args.has_flag("h") || args.positional.is_empty() {
+		print!("{}", USAGE);
+		return Ok(());
+	}
+
+	let subcommand = args.positional[0].as_str();
+	let file_path = args.positional.get(1).ok_or_else(|| format!("Missing file for {}\n\n{}", subcommand, USAGE))?;
+
+	match subcommand {
+		"info" => info_command(file_path),
+		"render" => render_command(file_path, &args),
+		"patterns" => patterns_command(file_path, &args),
+		"probe" => probe_command(file_path),
+		_ => Err(format!("Unknown subcommand {:?}\n\n{}", subcommand, USAGE)),
+	}
+}
+
+/// Parsed command-line arguments.
+#[derive(Debug, Default, PartialEq)]
+struct Args {
+	positional: Vec<String>,
+	options: HashMap<String, Vec<String>>,
+	flags: Vec<String>,
+}
+
+impl Args {
+	fn parse(args: &[String]) -> Result<Args, String> {
+		let mut parsed = Args::default();
+		let mut args = args.iter();
+
+		while let Some(arg) = args.next() {
+			let name = if let Some(name) = arg.strip_prefix("--") {
+				name
+			} else if let Some(name) = arg.strip_prefix('-').filter(|name| !name.is_empty()) {
+				name
+			} else {
+				parsed.positional.push(arg.clone());
+				continue;
+			};
+
+			let (name, value) = match name.find('=') {
+				Some(split) => (&name[..split], Some(name[split + 1..].to_owned())),
+				None => (name, None),
+			};
+			let name = if name == "o" { "output" } else { name };
+
+			if FLAGS.contains(&name) {
+				parsed.flags.push(name.to_owned());
+				continue;
+			}
+
+			let value = match value {
+				Some(value) => value,
+				None => args.next().cloned().ok_or_else(|| format!("Missing value for --{}", name))?,
+			};
+
+			parsed.options.entry(name.to_owned()).or_insert_with(Vec::new).push(value);
+		}
+
+		Ok(parsed)
+	}
+
+	fn has_flag(&self, name: &str) -> bool {
+		self.flags.iter().any(|flag| flag == name)
+	}
+
+	fn get(&self, name: &str) -> Option<&str> {
+		self.options.get(name).and_then(|values| values.last()).map(|value| value.as_str())
+	}
+
+	fn get_all(&self, name: &str) -> &[String] {
+		self.options.get(name).map(|values| values.as_slice()).unwrap_or(&[])
+	}
+
+	fn parse_value<T: FromStr>(&self, name: &str) -> Result<Option<T>, String> {
+		match self.get(name) {
+			Some(value) => T::from_str(value).map(Some).map_err(|_| format!("Invalid value {:?} for --{}", value, name)),
+			None => Ok(None),
+		}
+	}
+}
+
+fn load_module(file_path: &str, init_ctls: &[Ctl]) -> Result<Module, String> {
+	let mut file = File::open(file_path).map_err(|e| format!("Cannot open {}: {}", file_path, e))?;
+	Module::create(&mut file, Logger::StdErr, init_ctls).map_err(|_| format!("libopenmpt could not load {}", file_path))
+}
+
+fn info_command(file_path: &str) -> Result<(), String> {
+	let mut module = load_module(file_path, &[])?;
+	let metadata = module.metadata();
+	let inventory = module.inventory().map_err(|e| e.to_string())?;
+
+	println!("Format:      {} ({})", metadata.type_name, metadata.type_ext);
+	if let Some(ref container) = metadata.container {
+		println!("Container:   {} ({})", container.name, container.ext);
+	}
+	println!("Title:       {}", metadata.title);
+	println!("Artist:      {}", metadata.artist);
+	println!("Tracker:     {}", metadata.tracker);
+	if let Some(ref date) = metadata.save_date {
+		println!("Saved:       {}", date);
+	}
+	for (key, value) in &metadata.extra {
+		println!("{:<12} {}", format!("{}:", key), value);
+	}
+
+	println!("Channels:    {}", inventory.channels.len());
+	println!("Patterns:    {}", inventory.num_patterns());
+	println!("Orders:      {}", inventory.orders.len());
+	println!("Instruments: {}", inventory.instruments.len());
+	println!("Samples:     {}", inventory.samples.len());
+
+	println!("\nSub-songs:");
+	for (num, subsong) in inventory.subsongs.iter().enumerate() {
+		println!("  {:>3} {:>9} {}", num, format_duration(subsong.duration_seconds), subsong.name);
+	}
+
+	print_names("Instruments", &inventory.instruments);
+	print_names("Samples", &inventory.samples);
+
+	if !metadata.message.is_empty() {
+		println!("\nMessage:\n{}", metadata.message);
+	}
+
+	for warning in &metadata.warnings {
+		eprintln!("Warning: {}", warning);
+	}
+
+	Ok(())
+}
+
+fn print_names(title: &str, names: &[String]) {
+	if names.iter().all(|name| name.trim().is_empty()) {
+		return;
+	}
+
+	println!("\n{}:", title);
+	for (num, name) in names.iter().enumerate() {
+		println!("  {:>3} {}", num + 1, name);
+	}
+}
+
+fn format_duration(seconds: f64) -> String {
+	let total_ms = (seconds * 1000.0).round() as u64;
+	format!("{}:{:02}.{:03}", total_ms / 60000, (total_ms / 1000) % 60, total_ms % 1000)
+}
+
+fn render_ctls(args: &Args) -> Result<Vec<Ctl>, String> {
+	let mut ctls = Vec::new();
+
+	if let Some(factor) = args.parse_value("tempo-factor")? {
+		ctls.push(Ctl::PlaybackTempoFactor(factor));
+	}
+	if let Some(factor) = args.parse_value("pitch-factor")? {
+		ctls.push(Ctl::PlaybackPitchFactor(factor));
+	}
+	if let Some(mode) = args.parse_value::<DitherMode>("dither")? {
+		ctls.push(Ctl::DitherMode16Bit(mode));
+	}
+	if let Some(behavior) = args.parse_value::<AtEndBehavior>("at-end")? {
+		ctls.push(Ctl::AtEnd(behavior));
+	}
+	if args.has_flag("amiga") {
+		ctls.push(Ctl::EmulateAmigaResampler(true));
+	}
+	if let Some(filter) = args.parse_value::<AmigaFilterType>("amiga-filter")? {
+		ctls.push(Ctl::AmigaResamplerFilter(filter));
+	}
+
+	Ok(ctls)
+}
+
+// Ctls that only apply while loading, and have to be passed to Module::create
+fn loading_ctl(key: &str, value: &str) -> Result<Option<Ctl>, String> {
+	let ctl: fn(bool) -> Ctl = match key {
+		"load.skip_samples" => Ctl::SkipLoadingSamples,
+		"load.skip_patterns" => Ctl::SkipLoadingPatterns,
+		"load.skip_plugins" => Ctl::SkipLoadingPlugins,
+		"load.skip_subsongs_init" => Ctl::SkipSubsongPreinit,
+		_ if key.starts_with("load.") => return Err(format!("Unknown loading ctl {:?}", key)),
+		_ => return Ok(None),
+	};
+
+	match CtlValue::parse(CtlType::Boolean, value) {
+		Some(CtlValue::Boolean(value)) => Ok(Some(ctl(value))),
+		_ => Err(format!("Invalid value {:?} for ctl {}, expected 0 or 1", value, key)),
+	}
+}
+
+fn render_command(file_path: &str, args: &Args) -> Result<(), String> {
+	let sample_rate: i32 = args.parse_value("rate")?.unwrap_or(48000);
+	if sample_rate <= 0 {
+		return Err(format!("Invalid sample rate {}, it must be positive", sample_rate));
+	}
+
+	let max_seconds: Option<f64> = args.parse_value("max-seconds")?;
+	if max_seconds.is_some_and(|seconds| seconds.is_nan() || seconds <= 0.0) {
+		return Err("--max-seconds must be positive".to_owned());
+	}
+
+	let repeat: Option<i32> = args.parse_value("repeat")?;
+	if repeat.is_some_and(|repeat| repeat < 0) && max_seconds.is_none() {
+		return Err("--repeat -1 renders forever, it requires --max-seconds".to_owned());
+	}
+
+	let mut init_ctls = render_ctls(args)?;
+	let mut other_ctls = Vec::new();
+
+	for ctl in args.get_all("ctl") {
+		let split = ctl.find('=').ok_or_else(|| format!("Invalid --ctl {:?}, expected key=value", ctl))?;
+		let (key, value) = (&ctl[..split], &ctl[split + 1..]);
+
+		match loading_ctl(key, value)? {
+			Some(loading_ctl) => init_ctls.push(loading_ctl),
+			None => other_ctls.push((key, value)),
+		}
+	}
+
+	for ctl in &init_ctls {
+		ctl.validate().map_err(|e| e.to_string())?;
+	}
+
+	let mut module = load_module(file_path, &init_ctls)?;
+
+	for (key, value) in other_ctls {
+		module.ctl_set_value(key, &CtlValue::Text(value.to_owned()))
+			.map_err(|e| format!("Invalid --ctl {}={}: {}", key, value, e))?;
+	}
+
+	if let Some(subsong) = args.parse_value("subsong")? {
+		if !module.select_subsong(subsong) {
+			return Err(format!("Invalid sub-song {}", subsong));
+		}
+	}
+	if let Some(repeat) = repeat {
+		module.set_repeat_count(repeat);
+	}
+	if let Some(gain) = args.parse_value("gain")? {
+		module.set_render_mastergain_millibel(gain);
+	}
+	if let Some(separation) = args.parse_value("stereo-separation")? {
+		module.set_render_stereo_separation(separation);
+	}
+	if let Some(length) = args.parse_value("interpolation")? {
+		module.set_render_interpolation_filter_length(length);
+	}
+	if let Some(strength) = args.parse_value("volume-ramping")? {
+		module.set_render_volume_ramping(strength);
+	}
+
+	let format = args.get("format").unwrap_or("wav");
+	let is_wav = match format {
+		"wav" => true,
+		"raw" => false,
+		_ => return Err(format!("Unknown format {:?}", format)),
+	};
+
+	let max_frames = max_seconds.map(|seconds| (seconds * f64::from(sample_rate)) as usize);
+
+	let output_path = args.get("output").map(|path| path.to_owned())
+		.unwrap_or_else(|| format!("{}.{}", file_path, format));
+	let file = File::create(&output_path).map_err(|e| format!("Cannot create {}: {}", output_path, e))?;
+	let mut output = BufWriter::new(file);
+
+	let write_error = |e: io::Error| format!("Cannot write {}: {}", output_path, e);
+
+	if is_wav {
+		output.write_all(&wav_header(sample_rate as u32, 0)).map_err(write_error)?;
+	}
+
+	let mut buffer = vec![0i16; 2 * 4096];
+	let mut total_frames = 0;
+
+	let end_behavior = loop {
+		if max_frames.is_some_and(|max_frames| total_frames >= max_frames) {
+			break None;
+		}
+
+		let frames = module.read_interleaved_stereo(sample_rate, &mut buffer);
+		let frames = match module.read_status(frames, buffer.len() / 2).map_err(|e| format!("Cannot render {}: {}", file_path, e))? {
+			ReadStatus::Rendered(frames) => frames,
+			ReadStatus::Ended(behavior) => break Some(behavior),
+			ReadStatus::EmptyBuffer => unreachable!("the buffer always holds some frames"),
+		};
+		let frames = max_frames.map_or(frames, |max_frames| frames.min(max_frames - total_frames));
+		if is_wav && total_frames + frames > MAX_WAV_FRAMES {
+			return Err(format!("{} is too long for a WAV file, use --format raw or --max-seconds", output_path));
+		}
+
+		for sample in &buffer[..frames * 2] {
+			output.write_all(&sample.to_le_bytes()).map_err(write_error)?;
+		}
+
+		total_frames += frames;
+	};
+
+	if is_wav {
+		let mut file = output.into_inner().map_err(|e| write_error(e.into_error()))?;
+		file.seek(SeekFrom::Start(0)).map_err(write_error)?;
+		file.write_all(&wav_header(sample_rate as u32, (total_frames * 4) as u32)).map_err(write_error)?;
+	} else {
+		output.flush().map_err(write_error)?;
+	}
+
+	let reason = match end_behavior {
+		Some(behavior) => format!("end of song, {:?}", behavior).to_lowercase(),
+		None => "time limit".to_owned(),
+	};
+	eprintln!("Rendered {} to {} ({}, stopped by {})", format_duration(total_frames as f64 / f64::from(sample_rate)), output_path, format, reason);
+
+	Ok(())
+}
+
+// 16 bit stereo PCM
+fn wav_header(sample_rate: u32, data_size: u32) -> Vec<u8> {
+	let mut header = Vec::with_capacity(44);
+
+	header.extend_from_slice(b"RIFF");
+	header.extend_from_slice(&(36 + data_size).to_le_bytes());
+	header.extend_from_slice(b"WAVEfmt ");
+	header.extend_from_slice(&16u32.to_le_bytes()); // fmt chunk size
+	header.extend_from_slice(&1u16.to_le_bytes()); // PCM
+	header.extend_from_slice(&2u16.to_le_bytes()); // Channels
+	header.extend_from_slice(&sample_rate.to_le_bytes());
+	header.extend_from_slice(&(sample_rate * 4).to_le_bytes()); // Byte rate
+	header.extend_from_slice(&4u16.to_le_bytes()); // Block align
+	header.extend_from_slice(&16u16.to_le_bytes()); // Bits per sample
+	header.extend_from_slice(b"data");
+	header.extend_from_slice(&data_size.to_le_bytes());
+
+	header
+}
+
+fn patterns_command(file_path: &str, args: &Args) -> Result<(), String> {
+	let mut module = load_module(file_path, &[])?;
+
+	let style = match args.get("color").unwrap_or("none") {
+		"none" => HighlightStyle::Plain,
+		"ansi" => HighlightStyle::Ansi,
+		"html" => HighlightStyle::Html,
+		color => return Err(format!("Unknown color style {:?}", color)),
+	};
+	let channels = match args.get("channels") {
+		Some(channels) => Some(channels.split(',')
+			.map(|channel| channel.trim().parse().map_err(|_| format!("Invalid channel {:?}", channel)))
+			.collect::<Result<Vec<i32>, String>>()?),
+		None => None,
+	};
+	let options = PatternTextOptions {
+		style,
+		cell_width: args.parse_value("width")?.unwrap_or(13),
+		channels,
+		row_numbers: true,
+		separator: " |".to_owned(),
+	};
+
+	let pattern_nums: Vec<i32> = if let Some(pattern) = args.parse_value("pattern")? {
+		vec![pattern]
+	} else if let Some(order) = args.parse_value::<i32>("order")? {
+		vec![module.orders().nth(order as usize).ok_or_else(|| format!("Invalid order {}", order))?]
+	} else {
+		(0..module.get_num_patterns()).collect()
+	};
+
+	let stdout = io::stdout();
+	let mut out = stdout.lock();
+
+	for pattern_num in pattern_nums {
+		let mut pattern = module.get_pattern_by_number(pattern_num).ok_or_else(|| format!("Invalid pattern {}", pattern_num))?;
+		let name = pattern.get_name().map_err(|e| e.to_string())?;
+		let num_rows = pattern.get_num_rows();
+		let text = module.render_pattern(pattern_num, &options).map_err(|e| e.to_string())?;
+
+		writeln!(out, "Pattern {} ({} rows) {}", pattern_num, num_rows, name).map_err(|e| e.to_string())?;
+		writeln!(out, "{}", text).map_err(|e| e.to_string())?;
+	}
+
+	Ok(())
+}
+
+fn probe_command(file_path: &str) -> Result<(), String> {
+	let open_file = || File::open(file_path).map_err(|e| format!("Cannot open {}: {}", file_path, e));
+
+	let extension = file_path.rsplit('.').next().unwrap_or("");
+	println!("{:<32} {}", "Extension supported:", info::is_extension_supported(extension));
+
+	let efforts = [
+		("NoEffort", CouldOpenEffort::NoEffort),
+		("ProbeFileHeader", CouldOpenEffort::ProbeFileHeader),
+		("VerifyHeader", CouldOpenEffort::VerifyHeader),
+		("LoadWithoutPatternOrPluginData", CouldOpenEffort::LoadWithoutPatternOrPluginData),
+		("LoadCompleteModule", CouldOpenEffort::LoadCompleteModule),
+	];
+
+	for &(name, effort) in &efforts {
+		let probability = module::could_open_propability(&mut open_file()?, effort, Logger::None);
+		println!("{:<32} {:.3}", format!("{}:", name), probability);
+	}
+
+	let mut header = Vec::new();
+	let file = open_file()?;
+	let file_size = file.metadata().map_err(|e| e.to_string())?.len();
+	let recommended_size = probe::get_recommended_size();
+	file.take(recommended_size as u64).read_to_end(&mut header).map_err(|e| e.to_string())?;
+
+	match probe::probe_header_with_file_size(&header, ProbeFlags::DEFAULT, file_size) {
+		Ok(result) => println!("{:<32} {:?}", "Header probe:", result),
+		Err(error) => println!("{:<32} {}", "Header probe:", error),
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn to_args(args: &[&str]) -> Vec<String> {
+		args.iter().map(|arg| arg.to_string()).collect()
+	}
+
+	#[test]
+	fn arguments_are_parsed() {
+		let args = Args::parse(&to_args(&["render", "song.it", "-o", "out.wav", "--amiga", "--rate=44100", "--ctl", "a=1", "--ctl", "b=2"])).unwrap();
+
+		assert_eq!(args.positional, vec!["render", "song.it"]);
+		assert_eq!(args.get("output"), Some("out.wav"));
+		assert_eq!(args.parse_value::<i32>("rate"), Ok(Some(44100)));
+		assert_eq!(args.get_all("ctl"), &["a=1".to_owned(), "b=2".to_owned()]);
+		assert!(args.has_flag("amiga"));
+
+		assert!(Args::parse(&to_args(&["render", "song.it", "--rate"])).is_err());
+		assert!(args.parse_value::<i32>("output").is_err());
+	}
+
+	#[test]
+	fn render_options_map_to_ctls() {
+		let args = Args::parse(&to_args(&["--tempo-factor", "1.5", "--dither", "0", "--at-end", "stop", "--amiga", "--amiga-filter", "a500"])).unwrap();
+		let ctls = render_ctls(&args).unwrap();
+
+		assert_eq!(ctls.len(), 5);
+		assert!(Args::parse(&to_args(&["--at-end", "never"])).map(|args| render_ctls(&args)).unwrap().is_err());
+	}
+
+	#[test]
+	fn wav_header_is_valid() {
+		let header = wav_header(48000, 400);
+
+		assert_eq!(header.len(), 44);
+		assert_eq!(&header[0..4], b"RIFF");
+		assert_eq!(&header[4..8], &436u32.to_le_bytes());
+		assert_eq!(&header[24..28], &48000u32.to_le_bytes());
+		assert_eq!(&header[40..44], &400u32.to_le_bytes());
+
+		// The RIFF chunk size of the longest allowed output still fits
+		assert!(MAX_WAV_FRAMES as u64 * 4 + 36 <= u64::from(u32::MAX));
+		assert_eq!(wav_header(48000, (MAX_WAV_FRAMES * 4) as u32).len(), 44);
+	}
+
+	#[test]
+	fn endless_rendering_is_rejected() {
+		let args = Args::parse(&to_args(&["--repeat", "-1"])).unwrap();
+		assert!(render_command("song.it", &args).unwrap_err().contains("--max-seconds"));
+	}
+
+	#[test]
+	fn invalid_rates_and_durations_are_rejected() {
+		for args in &[&["--rate", "0"][..], &["--rate", "-44100"], &["--max-seconds", "-1"], &["--max-seconds", "0"]] {
+			let args = Args::parse(&to_args(args)).unwrap();
+			assert!(render_command("song.it", &args).unwrap_err().contains("positive"));
+		}
+	}
+
+	#[test]
+	fn loading_ctls_are_passed_while_loading() {
+		assert!(matches!(loading_ctl("load.skip_plugins", "1"), Ok(Some(Ctl::SkipLoadingPlugins(true)))));
+		assert!(matches!(loading_ctl("load.skip_samples", "0"), Ok(Some(Ctl::SkipLoadingSamples(false)))));
+		assert!(matches!(loading_ctl("play.tempo_factor", "2"), Ok(None)));
+		assert!(loading_ctl("load.skip_samples", "maybe").is_err());
+		assert!(loading_ctl("load.everything", "1").is_err());
+	}
+
+	#[test]
+	fn durations_are_formatted() {
+		assert_eq!(format_duration(0.0), "0:00.000");
+		assert_eq!(format_duration(83.5), "1:23.500");
+	}
+}
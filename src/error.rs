@@ -0,0 +1,88 @@
+//! The error type shared by the fallible wrappers of this crate.
+
+use std::error;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+	/// The linked libopenmpt is too old to provide the requested feature.
+	///
+	/// Contains the name of the missing feature or interface.
+	Unsupported(&'static str),
+	/// libopenmpt refused to carry out the operation, usually
+	/// because one of the parameters was out of range.
+	///
+	/// Contains the name of the failed operation.
+	OperationFailed(&'static str),
+	/// An index was outside of the range of valid items.
+	IndexOutOfRange {
+		/// The kind of item being looked up (e.g. "instrument")
+		item: &'static str,
+		/// The requested index
+		index: i32,
+		/// The number of items available
+		count: i32,
+	},
+	/// libopenmpt returned a null pointer instead of a string,
+	/// usually because it failed to allocate it.
+	///
+	/// Contains the name of the queried string.
+	NullString(&'static str),
+	/// A string passed to libopenmpt contains a NUL byte,
+	/// which C strings cannot hold.
+	///
+	/// Contains the string.
+	InteriorNul(String),
+	/// The ctl is not supported by the linked libopenmpt.
+	///
+	/// Contains the ctl key.
+	UnknownCtl(String),
+	/// The ctl exists, but cannot be used that way
+	/// (wrong value type, value out of range, etc.).
+	InvalidCtl {
+		/// The ctl key
+		key: String,
+		/// Why the ctl could not be used
+		reason: String,
+	},
+	/// A pattern cell holds data that cannot be decoded
+	/// (an invalid note value, for example).
+	InvalidCell {
+		/// The pattern number
+		pattern: i32,
+		/// The row of the cell in the pattern
+		row: i32,
+		/// The channel of the cell
+		channel: i32,
+		/// Why the cell could not be decoded
+		reason: String,
+	},
+}
+
+impl Error {
+	/// Check that `index` is in `[0, count)`, for an item of the given kind.
+	pub(crate) fn check_index(item: &'static str, index: i32, count: i32) -> Result<(), Error> {
+		if index < 0 || index >= count {
+			Err(Error::IndexOutOfRange { item, index, count })
+		} else {
+			Ok(())
+		}
+	}
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Error::Unsupported(feature) => write!(f, "{} is unsupported by linked libopenmpt", feature),
+			Error::OperationFailed(operation) => write!(f, "libopenmpt failed to {}", operation),
+			Error::IndexOutOfRange { item, index, count } => write!(f, "{} index {} is out of range (found {})", item, index, count),
+			Error::NullString(name) => write!(f, "libopenmpt returned no string for {}", name),
+			Error::InteriorNul(ref string) => write!(f, "{:?} contains a NUL byte", string),
+			Error::UnknownCtl(ref key) => write!(f, "ctl {:?} is unsupported by linked libopenmpt", key),
+			Error::InvalidCtl { ref key, ref reason } => write!(f, "invalid use of ctl {:?}: {}", key, reason),
+			Error::InvalidCell { pattern, row, channel, ref reason } => write!(f, "invalid cell in pattern {}, row {}, channel {}: {}", pattern, row, channel, reason),
+		}
+	}
+}
+
+impl error::Error for Error {}
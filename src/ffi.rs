@@ -0,0 +1,122 @@
+//! Declarations for the parts of the libopenmpt C API that are
+//! not (yet) covered by openmpt_sys.
+//!
+//! Everything in here mirrors libopenmpt.h and libopenmpt_ext.h,
+//! and should be removed once the sys crate exposes it.
+
+#![allow(non_camel_case_types)]
+
+use openmpt_sys;
+use std::os::raw::*;
+
+pub type openmpt_error_func = Option<unsafe extern "C" fn(error: c_int, user: *mut c_void) -> c_int>;
+
+pub enum openmpt_module_ext {}
+
+pub const ERROR_OK:c_int = 0;
+
+pub const PROBE_FILE_HEADER_RESULT_SUCCESS:c_int = 1;
+pub const PROBE_FILE_HEADER_RESULT_FAILURE:c_int = 0;
+pub const PROBE_FILE_HEADER_RESULT_WANTMOREDATA:c_int = -1;
+
+pub const INTERFACE_INTERACTIVE2:&str = "interactive2";
+pub const INTERFACE_INTERACTIVE3:&str = "interactive3";
+
+#[repr(C)]
+#[derive(Default)]
+pub struct openmpt_module_ext_interface_interactive2 {
+	pub note_off: Option<unsafe extern "C" fn(mod_ext: *mut openmpt_module_ext, channel: i32) -> c_int>,
+	pub note_fade: Option<unsafe extern "C" fn(mod_ext: *mut openmpt_module_ext, channel: i32) -> c_int>,
+	pub set_channel_panning: Option<unsafe extern "C" fn(mod_ext: *mut openmpt_module_ext, channel: i32, panning: c_double) -> c_int>,
+	pub get_channel_panning: Option<unsafe extern "C" fn(mod_ext: *mut openmpt_module_ext, channel: i32) -> c_double>,
+	pub set_note_finetune: Option<unsafe extern "C" fn(mod_ext: *mut openmpt_module_ext, channel: i32, finetune: c_double) -> c_int>,
+	pub get_note_finetune: Option<unsafe extern "C" fn(mod_ext: *mut openmpt_module_ext, channel: i32) -> c_double>,
+}
+
+#[repr(C)]
+#[derive(Default)]
+pub struct openmpt_module_ext_interface_interactive3 {
+	pub set_current_tempo2: Option<unsafe extern "C" fn(mod_ext: *mut openmpt_module_ext, tempo: c_double) -> c_int>,
+}
+
+extern "C" {
+	pub fn openmpt_module_ext_create(
+		stream_callbacks: openmpt_sys::openmpt_stream_callbacks,
+		stream: *mut c_void,
+		logfunc: openmpt_sys::openmpt_log_func,
+		loguser: *mut c_void,
+		errfunc: openmpt_error_func,
+		erruser: *mut c_void,
+		error: *mut c_int,
+		error_message: *mut *const c_char,
+		ctls: *const openmpt_sys::openmpt_module_initial_ctl,
+	) -> *mut openmpt_module_ext;
+
+	pub fn openmpt_module_ext_create_from_memory(
+		filedata: *const c_void,
+		filesize: usize,
+		logfunc: openmpt_sys::openmpt_log_func,
+		loguser: *mut c_void,
+		errfunc: openmpt_error_func,
+		erruser: *mut c_void,
+		error: *mut c_int,
+		error_message: *mut *const c_char,
+		ctls: *const openmpt_sys::openmpt_module_initial_ctl,
+	) -> *mut openmpt_module_ext;
+
+	pub fn openmpt_module_ext_destroy(mod_ext: *mut openmpt_module_ext);
+
+	pub fn openmpt_module_ext_get_module(mod_ext: *mut openmpt_module_ext) -> *mut openmpt_sys::openmpt_module;
+
+	pub fn openmpt_probe_file_header_get_recommended_size() -> usize;
+
+	pub fn openmpt_probe_file_header(
+		flags: u64,
+		data: *const c_void,
+		size: usize,
+		filesize: u64,
+		logfunc: openmpt_sys::openmpt_log_func,
+		loguser: *mut c_void,
+		errfunc: openmpt_error_func,
+		erruser: *mut c_void,
+		error: *mut c_int,
+		error_message: *mut *const c_char,
+	) -> c_int;
+
+	pub fn openmpt_probe_file_header_without_filesize(
+		flags: u64,
+		data: *const c_void,
+		size: usize,
+		logfunc: openmpt_sys::openmpt_log_func,
+		loguser: *mut c_void,
+		errfunc: openmpt_error_func,
+		erruser: *mut c_void,
+		error: *mut c_int,
+		error_message: *mut *const c_char,
+	) -> c_int;
+
+	pub fn openmpt_module_error_get_last(mod_: *mut openmpt_sys::openmpt_module) -> c_int;
+	pub fn openmpt_module_error_clear(mod_: *mut openmpt_sys::openmpt_module);
+
+	pub fn openmpt_module_ctl_get_boolean(mod_: *mut openmpt_sys::openmpt_module, ctl: *const c_char) -> c_int;
+	pub fn openmpt_module_ctl_get_integer(mod_: *mut openmpt_sys::openmpt_module, ctl: *const c_char) -> i64;
+	pub fn openmpt_module_ctl_get_floatingpoint(mod_: *mut openmpt_sys::openmpt_module, ctl: *const c_char) -> c_double;
+
+	pub fn openmpt_module_ctl_set_boolean(mod_: *mut openmpt_sys::openmpt_module, ctl: *const c_char, value: c_int) -> c_int;
+	pub fn openmpt_module_ctl_set_integer(mod_: *mut openmpt_sys::openmpt_module, ctl: *const c_char, value: i64) -> c_int;
+	pub fn openmpt_module_ctl_set_floatingpoint(mod_: *mut openmpt_sys::openmpt_module, ctl: *const c_char, value: c_double) -> c_int;
+
+	pub fn openmpt_module_get_selected_subsong(mod_: *mut openmpt_sys::openmpt_module) -> i32;
+
+	#[cfg(feature = "libopenmpt-0-8")]
+	pub fn openmpt_module_get_pattern_rows_per_beat(mod_: *mut openmpt_sys::openmpt_module, pattern: i32) -> i32;
+	#[cfg(feature = "libopenmpt-0-8")]
+	pub fn openmpt_module_get_pattern_rows_per_measure(mod_: *mut openmpt_sys::openmpt_module, pattern: i32) -> i32;
+
+	pub fn openmpt_module_ext_get_interface(
+		mod_ext: *mut openmpt_module_ext,
+		interface_id: *const c_char,
+		interface: *mut c_void,
+		interface_size: usize,
+	) -> c_int;
+}
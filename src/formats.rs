@@ -0,0 +1,230 @@
+//! A registry of the file formats supported by libopenmpt, built from
+//! `info::get_supported_extensions` and a table of known tracker names.
+
+use error::Error;
+use openmpt_sys;
+
+/// A file format supported by libopenmpt.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize))]
+pub struct FormatInfo {
+	/// File extension, lower-case and without a leading dot (e.g. it)
+	pub extension: String,
+	/// Name of the tracker or format (e.g. Impulse Tracker), None if unknown to this crate
+	pub tracker_name: Option<String>,
+	/// Whether this is a container format wrapping other modules (e.g. umx)
+	pub is_container: bool,
+}
+
+// Tracker names for the module extensions reported by libopenmpt.
+// Extensions missing from here (from a newer libopenmpt) are still listed, without a name.
+const KNOWN_FORMATS:[(&str, &str); 65] = [
+	("mptm", "OpenMPT"),
+	("mod", "ProTracker"),
+	("s3m", "Scream Tracker 3"),
+	("xm", "FastTracker II"),
+	("it", "Impulse Tracker"),
+	("667", "Composer 667"),
+	("669", "Composer 669 / UNIS 669"),
+	("amf", "ASYLUM Music Format / DSMI Advanced Music Format"),
+	("ams", "Extreme's Tracker / Velvet Studio"),
+	("c67", "CDFM Composer"),
+	("cba", "Chuck Biscuits / Black Artist"),
+	("dbm", "DigiBooster Pro"),
+	("digi", "DigiBooster"),
+	("dmf", "X-Tracker"),
+	("dsm", "DSIK Format / Dynamic Studio"),
+	("dsym", "Digital Symphony"),
+	("dtm", "Digital Tracker / Digital Home Studio"),
+	("etx", "EasyTrax"),
+	("far", "Farandole Composer"),
+	("fc", "Future Composer"),
+	("fc13", "Future Composer 1.3"),
+	("fc14", "Future Composer 1.4"),
+	("fmt", "FM Tracker"),
+	("ftm", "Face The Music"),
+	("gdm", "General Digital Music"),
+	("gmc", "Game Music Creator"),
+	("gt2", "Graoumf Tracker 2"),
+	("gtk", "Graoumf Tracker"),
+	("ice", "Ice Tracker"),
+	("imf", "Imago Orpheus"),
+	("ims", "Images Music System"),
+	("itp", "Impulse Tracker Project"),
+	("j2b", "Galaxy Sound System"),
+	("kris", "ChipTracker"),
+	("m15", "Ultimate Soundtracker"),
+	("mdl", "Digitrakker"),
+	("med", "OctaMED"),
+	("mms", "MultiMedia Sound"),
+	("mo3", "MO3"),
+	("mt2", "MadTracker 2"),
+	("mtm", "MultiTracker"),
+	("mus", "Karl Morton Music Format"),
+	("nst", "NoiseTracker"),
+	("okt", "Oktalyzer"),
+	("oxm", "OggMod FastTracker II"),
+	("plm", "Disorder Tracker 2"),
+	("psm", "Epic MegaGames MASI"),
+	("pt36", "ProTracker 3.6"),
+	("ptm", "PolyTracker"),
+	("puma", "PumaTracker"),
+	("rtm", "Real Tracker 2"),
+	("sfx", "SoundFX"),
+	("sfx2", "SoundFX 2"),
+	("smod", "Sound Smith / Copyist"),
+	("st26", "SoundTracker 2.6"),
+	("stk", "Soundtracker"),
+	("stm", "Scream Tracker 2"),
+	("stp", "Soundtracker Pro II"),
+	("stx", "Scream Tracker Music Interface Kit"),
+	("symmod", "Symphonie"),
+	("tcb", "TCB Tracker"),
+	("ult", "UltraTracker"),
+	("unic", "UNIC Tracker"),
+	("wow", "Mod's Grave"),
+	("xmf", "Astroidea XMF"),
+];
+
+// Containers wrapping other modules, including the zip-based ones only supported by
+// some libopenmpt builds, and the packers libopenmpt unpacks before loading
+const KNOWN_CONTAINERS:[(&str, &str); 10] = [
+	("umx", "Unreal Music"),
+	("mdz", "Zipped ProTracker"),
+	("mdr", "RAR-compressed ProTracker"),
+	("s3z", "Zipped Scream Tracker 3"),
+	("xmz", "Zipped FastTracker II"),
+	("itz", "Zipped Impulse Tracker"),
+	("mptmz", "Zipped OpenMPT"),
+	("xpk", "XPK packed"),
+	("ppm", "PowerPacker"),
+	("mmcmp", "Music Module Compressor"),
+];
+
+/// The formats supported by the linked libopenmpt.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Formats {
+	formats: Vec<FormatInfo>,
+}
+
+impl Formats {
+	/// Query the formats supported by the linked libopenmpt.
+	pub fn new() -> Result<Formats, Error> {
+		let opt_string = get_string!{
+			openmpt_sys::openmpt_get_supported_extensions()
+		};
+
+		opt_string
+			.map(|extensions| Formats::from_extension_list(&extensions))
+			.ok_or(Error::NullString("supported extensions"))
+	}
+
+	/// Build the registry from a semicolon-separated list of extensions,
+	/// as returned by `info::get_supported_extensions`.
+	pub fn from_extension_list(extensions: &str) -> Formats {
+		let formats = extensions.split(';')
+			.filter(|extension| !extension.is_empty())
+			.map(format_info)
+			.collect();
+
+		Formats { formats }
+	}
+
+	/// Get every supported format, in the order reported by libopenmpt.
+	pub fn all(&self) -> &[FormatInfo] {
+		&self.formats
+	}
+
+	/// Get every supported format, consuming the registry.
+	pub fn into_vec(self) -> Vec<FormatInfo> {
+		self.formats
+	}
+
+	/// Look up a format by extension.
+	///
+	/// ### Parameters
+	/// * `extension` : File extension to look up, with or without a leading dot. Case-insensitive.
+	pub fn get(&self, extension: &str) -> Option<&FormatInfo> {
+		let extension = extension.trim_start_matches('.');
+		self.formats.iter().find(|format| format.extension.eq_ignore_ascii_case(extension))
+	}
+
+	/// Get the module formats, leaving out containers.
+	pub fn modules(&self) -> Vec<&FormatInfo> {
+		self.formats.iter().filter(|format| !format.is_container).collect()
+	}
+
+	/// Get the container formats.
+	pub fn containers(&self) -> Vec<&FormatInfo> {
+		self.formats.iter().filter(|format| format.is_container).collect()
+	}
+}
+
+fn format_info(extension: &str) -> FormatInfo {
+	let extension = extension.to_lowercase();
+	let find = |table: &[(&str, &'static str)]| {
+		table.iter().find(|&&(known_ext, _)| known_ext == extension).map(|&(_, name)| name)
+	};
+
+	let (tracker_name, is_container) = match find(&KNOWN_CONTAINERS) {
+		Some(name) => (Some(name), true),
+		None => (find(&KNOWN_FORMATS), false),
+	};
+
+	FormatInfo {
+		tracker_name: tracker_name.map(|name| name.to_owned()),
+		is_container,
+		extension,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use info;
+
+	#[test]
+	fn extension_list_is_parsed() {
+		let formats = Formats::from_extension_list("mod;XM;umx;mdz;xpk;newformat;");
+
+		assert_eq!(formats.all().len(), 6);
+		assert_eq!(formats.get("xm").unwrap().tracker_name, Some("FastTracker II".to_owned()));
+		assert_eq!(formats.get(".MOD").unwrap().extension, "mod");
+		assert!(formats.get("umx").unwrap().is_container);
+		assert!(formats.get("mdz").unwrap().is_container);
+		assert_eq!(formats.get("newformat").unwrap(), &FormatInfo { extension: "newformat".to_owned(), tracker_name: None, is_container: false });
+		assert!(formats.get("it").is_none());
+
+		assert_eq!(formats.get("xpk").unwrap().tracker_name, Some("XPK packed".to_owned()));
+		assert!(formats.get("xpk").unwrap().is_container);
+
+		assert_eq!(formats.modules().len(), 3);
+		assert_eq!(formats.containers().len(), 3);
+	}
+
+	#[test]
+	fn known_formats_are_unique() {
+		let known: Vec<&str> = KNOWN_FORMATS.iter().chain(KNOWN_CONTAINERS.iter()).map(|&(extension, _)| extension).collect();
+
+		for (i, extension) in known.iter().enumerate() {
+			assert!(!known[i + 1..].contains(extension), "{} is listed twice", extension);
+		}
+	}
+
+	#[test]
+	fn linked_formats_are_registered() {
+		let formats = Formats::new().unwrap();
+
+		assert_eq!(formats.get("it").unwrap().tracker_name, Some("Impulse Tracker".to_owned()));
+		assert!(formats.all().iter().all(|format| info::is_extension_supported(&format.extension)));
+
+		let extensions = info::get_supported_extensions();
+		let extensions: Vec<&str> = extensions.split(';').filter(|extension| !extension.is_empty()).collect();
+		assert_eq!(formats.all().len(), extensions.len());
+
+		for extension in extensions {
+			let format = formats.get(extension).unwrap();
+			assert!(format.tracker_name.is_some(), "{} has no name", extension);
+		}
+	}
+}
@@ -1,20 +1,137 @@
 //! Functions and types related to querying library information.
 
 use openmpt_sys;
+use error::Error;
+use std::fmt;
 use std::str;
+use std::str::FromStr;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 /// A struct containing the OpenMPT version number, in big-endian.
 ///
 /// `CoreVersion(majormajor, major, minor, minorminor)`
+///
+/// Each component is written in hexadecimal by OpenMPT (e.g. 1.28.01.00 is
+/// `CoreVersion(0x01, 0x28, 0x01, 0x00)`), which is how `Display` and `FromStr` treat them.
 pub struct CoreVersion (pub u8,	pub u8,	pub u8,	pub u8);
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 /// A struct containing the libopenmpt version number, in big-endian.
 ///
 /// `LibraryVersion(major, minor, revision)`
 pub struct LibraryVersion (pub u8, pub u8, pub u16);
 
+impl LibraryVersion {
+	/// Whether this version is at least `major.minor`, regardless of the revision.
+	pub fn at_least(&self, major: u8, minor: u8) -> bool {
+		(self.0, self.1) >= (major, minor)
+	}
+
+	/// Whether code written against `other` can be used with this version.
+	///
+	/// Follows semver rules : before 1.0, minor versions are considered breaking.
+	pub fn is_compatible_with(&self, other: &LibraryVersion) -> bool {
+		if self.0 == 0 {
+			self.0 == other.0 && self.1 == other.1 && self.2 >= other.2
+		} else {
+			self.0 == other.0 && *self >= *other
+		}
+	}
+}
+
+impl fmt::Display for CoreVersion {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{:X}.{:02X}.{:02X}.{:02X}", self.0, self.1, self.2, self.3)
+	}
+}
+
+impl fmt::Display for LibraryVersion {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}.{}.{}", self.0, self.1, self.2)
+	}
+}
+
+impl FromStr for CoreVersion {
+	type Err = String;
+
+	/// Parse a version such as `1.28.01.00`, as found in `InfoField::CoreVersion`.
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let parts = s.trim().split('.')
+			.map(|part| u8::from_str_radix(part, 16))
+			.collect::<Result<Vec<u8>, _>>()
+			.map_err(|_| format!("Invalid core version \"{}\"", s))?;
+
+		match parts.as_slice() {
+			[majormajor, major, minor, minorminor] => Ok(CoreVersion(*majormajor, *major, *minor, *minorminor)),
+			_ => Err(format!("Invalid core version \"{}\"", s)),
+		}
+	}
+}
+
+impl FromStr for LibraryVersion {
+	type Err = String;
+
+	/// Parse a version such as `0.4.11` or `0.8.0-pre.3+r12345`, as found in
+	/// `InfoField::LibraryVersion`. Pre-release and build suffixes are ignored,
+	/// and a missing revision is treated as 0.
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let invalid = || format!("Invalid library version \"{}\"", s);
+		let version = s.trim().split(&['-', '+'][..]).next().unwrap_or("");
+		let mut parts = version.split('.');
+
+		let major = parts.next().and_then(|part| u8::from_str(part).ok()).ok_or_else(invalid)?;
+		let minor = parts.next().and_then(|part| u8::from_str(part).ok()).ok_or_else(invalid)?;
+		let revision = match parts.next() {
+			Some(part) => u16::from_str(part).map_err(|_| invalid())?,
+			None => 0,
+		};
+
+		if parts.next().is_some() {
+			return Err(invalid());
+		}
+
+		Ok(LibraryVersion(major, minor, revision))
+	}
+}
+
+/// The optional features libopenmpt was built with, parsed from `InfoField::LibraryFeatures`.
+///
+/// Features are listed as `+NAME` when enabled and `-NAME` when disabled
+/// (e.g. `+ZLIB -MPG123`). Anything else (such as the C++ standard) is kept in `other`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct LibraryFeatures {
+	pub enabled: Vec<String>,
+	pub disabled: Vec<String>,
+	pub other: Vec<String>,
+}
+
+impl LibraryFeatures {
+	/// Whether a feature is listed as enabled. Case-insensitive.
+	pub fn has(&self, feature: &str) -> bool {
+		self.enabled.iter().any(|enabled| enabled.eq_ignore_ascii_case(feature))
+	}
+}
+
+impl FromStr for LibraryFeatures {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let mut features = LibraryFeatures::default();
+
+		for token in s.split_whitespace() {
+			if token.starts_with('+') && token.len() > 1 {
+				features.enabled.push(token[1..].to_owned());
+			} else if token.starts_with('-') && token.len() > 1 {
+				features.disabled.push(token[1..].to_owned());
+			} else {
+				features.other.push(token.to_owned());
+			}
+		}
+
+		Ok(features)
+	}
+}
+
 #[derive(Debug)]
 /// An enum containing all the potentially valid keys for `openmpt_get_string`
 pub enum InfoField {
@@ -150,8 +267,89 @@ pub fn get_library_version () -> LibraryVersion {
 	)
 }
 
+/// Get the optional features of the libopenmpt build.
+///
+/// ### Returns
+/// The parsed feature list, or None if libopenmpt could not provide it.
+pub fn get_library_features() -> Option<LibraryFeatures> {
+	get_string(&InfoField::LibraryFeatures)
+		.and_then(|features| LibraryFeatures::from_str(&features).ok())
+}
+
+/// Check that the linked libopenmpt is recent enough for a feature.
+///
+/// ### Parameters
+/// * `min_version` : The first libopenmpt version providing the feature.
+/// * `feature` : Name of the feature, reported in the error.
+///
+/// ### Returns
+/// `Error::Unsupported` if the linked libopenmpt is older than `min_version`.
+pub fn require_version(min_version: LibraryVersion, feature: &'static str) -> Result<(), Error> {
+	if get_library_version() >= min_version {
+		Ok(())
+	} else {
+		Err(Error::Unsupported(feature))
+	}
+}
+
 #[cfg(test)]
 mod tests {
+	use super::*;
+
+	#[test]
+	fn versions_are_ordered() {
+		assert!(LibraryVersion(0, 4, 11) < LibraryVersion(0, 5, 0));
+		assert!(LibraryVersion(0, 5, 2) < LibraryVersion(0, 5, 10));
+		assert!(LibraryVersion(1, 0, 0) > LibraryVersion(0, 99, 999));
+		assert!(CoreVersion(0x01, 0x28, 0x01, 0x00) < CoreVersion(0x01, 0x29, 0x00, 0x00));
+
+		assert!(LibraryVersion(0, 5, 0).at_least(0, 4));
+		assert!(LibraryVersion(0, 5, 0).at_least(0, 5));
+		assert!(!LibraryVersion(0, 5, 0).at_least(0, 6));
+	}
+
+	#[test]
+	fn versions_are_semver_compatible() {
+		assert!(LibraryVersion(0, 5, 4).is_compatible_with(&LibraryVersion(0, 5, 1)));
+		assert!(!LibraryVersion(0, 5, 1).is_compatible_with(&LibraryVersion(0, 5, 4)));
+		assert!(!LibraryVersion(0, 6, 0).is_compatible_with(&LibraryVersion(0, 5, 0)));
+		assert!(LibraryVersion(1, 2, 0).is_compatible_with(&LibraryVersion(1, 1, 3)));
+		assert!(!LibraryVersion(2, 0, 0).is_compatible_with(&LibraryVersion(1, 1, 3)));
+	}
+
+	#[test]
+	fn versions_round_trip_through_strings() {
+		assert_eq!(LibraryVersion(0, 4, 11).to_string(), "0.4.11");
+		assert_eq!(LibraryVersion::from_str("0.4.11"), Ok(LibraryVersion(0, 4, 11)));
+		assert_eq!(LibraryVersion::from_str("0.8.0-pre.3+r12345"), Ok(LibraryVersion(0, 8, 0)));
+		assert_eq!(LibraryVersion::from_str("0.7"), Ok(LibraryVersion(0, 7, 0)));
+		assert!(LibraryVersion::from_str("0").is_err());
+		assert!(LibraryVersion::from_str("0.7.1.2").is_err());
+		assert!(LibraryVersion::from_str("libopenmpt").is_err());
+
+		assert_eq!(CoreVersion(0x01, 0x28, 0x01, 0x00).to_string(), "1.28.01.00");
+		assert_eq!(CoreVersion::from_str("1.28.01.00"), Ok(CoreVersion(0x01, 0x28, 0x01, 0x00)));
+		assert!(CoreVersion::from_str("1.28.01").is_err());
+	}
+
+	#[test]
+	fn library_features_are_parsed() {
+		let features = LibraryFeatures::from_str("C++17 +ZLIB -MPG123 +VORBIS -").unwrap();
+
+		assert_eq!(features.enabled, vec!["ZLIB", "VORBIS"]);
+		assert_eq!(features.disabled, vec!["MPG123"]);
+		assert_eq!(features.other, vec!["C++17", "-"]);
+		assert!(features.has("zlib"));
+		assert!(!features.has("MPG123"));
+	}
+
+	#[test]
+	fn linked_version_can_be_parsed() {
+		let version_string = get_string(&InfoField::LibraryVersion).unwrap();
+		assert_eq!(LibraryVersion::from_str(&version_string), Ok(get_library_version()));
+		assert!(require_version(get_library_version(), "current version").is_ok());
+	}
+
 	#[test]
 	fn try_lib_version_field() {
 		// Those should always return something
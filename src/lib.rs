@@ -1,10 +1,21 @@
 //! Safe rust bindings for libopenmpt, built on top of the C API.
 //!
 //! See openmpt_sys for the unsafe bindings.
+//!
+//! Requires libopenmpt 0.5 or newer. Features from later versions
+//! return `Error::Unsupported` when the library in use is too old.
+//! Functions added in libopenmpt 0.8 are only linked with the `libopenmpt-0-8`
+//! feature, and return `Error::Unsupported` without it.
 
 extern crate openmpt_sys;
+#[cfg(feature = "serde")] extern crate serde;
 
 #[macro_use] mod string_helper;
+mod ffi;
+pub mod error;
+pub mod formats;
 pub mod info;
 pub mod mod_command;
-pub mod module;
\ No newline at end of file
+pub mod module;
+pub mod probe;
+pub mod scan;
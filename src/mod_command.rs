@@ -5,6 +5,9 @@
 //! They are not part of the public API and change to OpenMPT may break those without warning.
 //! They are only available here for the sake of conveinience and completeness.
 
+use std::fmt;
+use std::str::FromStr;
+
 const NOTE_NONE:u8 = 0;
 const NOTE_MIN:u8 = 1;
 const NOTE_MAX:u8 = 120;
@@ -15,6 +18,11 @@ const NOTE_FADE:u8 = 0xFD;
 const NOTE_PC:u8 = 0xFC;
 const NOTE_PCS:u8 = 0xFB;
 
+const NOTE_NAMES:[&str; 12] = ["C-", "C#", "D-", "D#", "E-", "F-", "F#", "G-", "G#", "A-", "A#", "B-"];
+// A-5 in OpenMPT's notation, A4 in MIDI's
+const NOTE_A440:u8 = 5 * 12 + 9 + NOTE_MIN;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct ModCommand {
 	pub note : Note,
 	pub instr: u8,
@@ -34,8 +42,9 @@ impl ModCommand {
 	/// * `param` : The raw effect parameter
 	///
 	/// ### Returns
-	/// The resulting ModCommand, or an error message
-	/// if one of the parameter has an unknown or invalid value
+	/// The resulting ModCommand, or an error message if the note has an invalid value.
+	/// Volume and effect commands unknown to this crate are kept as
+	/// `VolumeCommand::Unknown` and `EffectCommand::Unknown`.
 	pub fn new(note : u8, instr : u8, volcmd : u8, command : u8, vol : u8, param : u8) -> Result<ModCommand, String> {
 		let note_type = ModCommand::note_from_value(note);
 		let note_type = match note_type {
@@ -63,7 +72,31 @@ impl ModCommand {
 		})
 	}
 
-	/// Returns the note index corresponding to a middle C (C4).
+	/// Encode a ModCommand back into pattern cell data.
+	///
+	/// ### Returns
+	/// The raw cell data, in the same order as the parameters of `new` :
+	///
+	/// `[note, instr, volcmd, command, vol, param]`
+	///
+	/// ### Remarks
+	/// Effects that read their parameter as 2 x and y values are re-packed
+	/// into a single byte, only keeping the lower nibble of each value.
+	///
+	/// Encoding a decoded cell gives back the original data, except for empty
+	/// volume and effect commands : `VolumeCommand::None` and `EffectCommand::None`
+	/// do not keep their parameter, and are always encoded with a parameter of 0.
+	/// A stray `vol` byte with a `volcmd` of 0 (or `param` with a `command` of 0)
+	/// is lost, which does not change how the cell is played.
+	pub fn to_raw(&self) -> [u8; 6] {
+		let note = ModCommand::note_to_value(&self.note);
+		let (volcmd, vol) = ModCommand::volume_to_command_param(&self.volcmd);
+		let (command, param) = ModCommand::effect_to_command_param(&self.command);
+
+		[note, self.instr, volcmd, command, vol, param]
+	}
+
+	/// Returns the note index corresponding to a middle C (C4 in MIDI, C-5 in OpenMPT).
 	pub fn middle_c() -> u8 {
 		NOTE_MIDDLEC
 	}
@@ -71,7 +104,7 @@ impl ModCommand {
 	fn note_from_value(note_val : u8) -> Result<Note, String> {
 		match note_val {
 			NOTE_NONE => Ok(Note::None),
-			NOTE_MIN...NOTE_MAX => Ok(Note::Note(note_val)),
+			NOTE_MIN...NOTE_MAX => Ok(Note::Note(Pitch(note_val))),
 			NOTE_KEYOFF => Ok(Note::Special(SpecialNote::KeyOff)),
 			NOTE_NOTECUT => Ok(Note::Special(SpecialNote::NoteCut)),
 			NOTE_FADE => Ok(Note::Special(SpecialNote::Fade)),
@@ -128,7 +161,7 @@ impl ModCommand {
 			39 => Ok(EffectCommand::ReverseOffset(param)),
 			40 => Ok(EffectCommand::DBMEcho(nibble_x, nibble_y)),
 			41 => Ok(EffectCommand::OffsetPercentage(param)),
-			_ => Err("Invalid effect".to_owned()),
+			_ => Ok(EffectCommand::Unknown(cmd, param)),
 		}
 	}
 
@@ -150,19 +183,105 @@ impl ModCommand {
 			13 => Ok(VolumeCommand::PortaDown(param)),
 			14 => Ok(VolumeCommand::DelayCut(param)),
 			15 => Ok(VolumeCommand::Offset(param)),
-			_  => Err("Invalid volume command".to_owned()),
+			_  => Ok(VolumeCommand::Unknown(cmd, param)),
+		}
+	}
+
+	fn note_to_value(note : &Note) -> u8 {
+		match *note {
+			Note::None => NOTE_NONE,
+			Note::Note(pitch) => pitch.value(),
+			Note::Special(SpecialNote::KeyOff) => NOTE_KEYOFF,
+			Note::Special(SpecialNote::NoteCut) => NOTE_NOTECUT,
+			Note::Special(SpecialNote::Fade) => NOTE_FADE,
+			Note::Special(SpecialNote::ParamControl) => NOTE_PC,
+			Note::Special(SpecialNote::ParamControlSmooth) => NOTE_PCS,
+		}
+	}
+
+	fn effect_to_command_param(effect : &EffectCommand) -> (u8, u8) {
+		let nibbles = |x : u8, y : u8| ((x & 0x0F) << 4) | (y & 0x0F);
+
+		match *effect {
+			EffectCommand::None => (0, 0),
+			EffectCommand::Arpeggio(x, y) => (1, nibbles(x, y)),
+			EffectCommand::PortamentoUp(param) => (2, param),
+			EffectCommand::PortamentoDown(param) => (3, param),
+			EffectCommand::TonePortamento(param) => (4, param),
+			EffectCommand::Vibrato(x, y) => (5, nibbles(x, y)),
+			EffectCommand::TonePortaVol(x, y) => (6, nibbles(x, y)),
+			EffectCommand::VibratoVol(x, y) => (7, nibbles(x, y)),
+			EffectCommand::Tremolo(x, y) => (8, nibbles(x, y)),
+			EffectCommand::Panning8(param) => (9, param),
+			EffectCommand::Offset(param) => (10, param),
+			EffectCommand::VolumeSlide(x, y) => (11, nibbles(x, y)),
+			EffectCommand::PositionJump(param) => (12, param),
+			EffectCommand::Volume(param) => (13, param),
+			EffectCommand::PatternBreak(param) => (14, param),
+			EffectCommand::Retrig(x, y) => (15, nibbles(x, y)),
+			EffectCommand::Speed(param) => (16, param),
+			EffectCommand::Tempo(param) => (17, param),
+			EffectCommand::Tremor(x, y) => (18, nibbles(x, y)),
+			EffectCommand::ModCmdEX(x, y) => (19, nibbles(x, y)),
+			EffectCommand::S3MCmdEX(x, y) => (20, nibbles(x, y)),
+			EffectCommand::ChannelVolume(param) => (21, param),
+			EffectCommand::ChannelVolSlide(x, y) => (22, nibbles(x, y)),
+			EffectCommand::GlobalVolume(param) => (23, param),
+			EffectCommand::GlobalVolSlide(x, y) => (24, nibbles(x, y)),
+			EffectCommand::KeyOff(param) => (25, param),
+			EffectCommand::FineVibrato(x, y) => (26, nibbles(x, y)),
+			EffectCommand::Panbrello(x, y) => (27, nibbles(x, y)),
+			EffectCommand::XFinePortaUpDown(x, y) => (28, nibbles(x, y)),
+			EffectCommand::PanningSlide(x, y) => (29, nibbles(x, y)),
+			EffectCommand::SetEnvPosition(param) => (30, param),
+			EffectCommand::Midi(param) => (31, param),
+			EffectCommand::SmoothMidi(param) => (32, param),
+			EffectCommand::DelayCut(x, y) => (33, nibbles(x, y)),
+			EffectCommand::XParam(param) => (34, param),
+			EffectCommand::NoteSlideUp(x, y) => (35, nibbles(x, y)),
+			EffectCommand::NoteSlideUpRetrig(x, y) => (36, nibbles(x, y)),
+			EffectCommand::NoteSlideDown(x, y) => (37, nibbles(x, y)),
+			EffectCommand::NoteSlideDownRetrig(x, y) => (38, nibbles(x, y)),
+			EffectCommand::ReverseOffset(param) => (39, param),
+			EffectCommand::DBMEcho(x, y) => (40, nibbles(x, y)),
+			EffectCommand::OffsetPercentage(param) => (41, param),
+			EffectCommand::Unknown(cmd, param) => (cmd, param),
+		}
+	}
+
+	fn volume_to_command_param(volume : &VolumeCommand) -> (u8, u8) {
+		match *volume {
+			VolumeCommand::None => (0, 0),
+			VolumeCommand::Volume(param) => (1, param),
+			VolumeCommand::Panning(param) => (2, param),
+			VolumeCommand::VolSlideUp(param) => (3, param),
+			VolumeCommand::VolSlideDown(param) => (4, param),
+			VolumeCommand::FineVolUp(param) => (5, param),
+			VolumeCommand::FineVolDown(param) => (6, param),
+			VolumeCommand::VibratoSpeed(param) => (7, param),
+			VolumeCommand::VibratoDepth(param) => (8, param),
+			VolumeCommand::PanSlideLeft(param) => (9, param),
+			VolumeCommand::PanSlideRight(param) => (10, param),
+			VolumeCommand::TonePortamento(param) => (11, param),
+			VolumeCommand::PortaUp(param) => (12, param),
+			VolumeCommand::PortaDown(param) => (13, param),
+			VolumeCommand::DelayCut(param) => (14, param),
+			VolumeCommand::Offset(param) => (15, param),
+			VolumeCommand::Unknown(cmd, param) => (cmd, param),
 		}
 	}
 }
 
 /// An enum containing the different value for Note commands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Note {
 	None,
-	Note(u8),
+	Note(Pitch),
 	Special(SpecialNote),
 }
 
 /// An enum containing the special values for Note commands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SpecialNote {
 	KeyOff,
 	NoteCut,
@@ -171,6 +290,115 @@ pub enum SpecialNote {
 	ParamControlSmooth,
 }
 
+/// A playable note, between C-0 and B-9.
+///
+/// Notes are named the way OpenMPT displays them, with the semitone
+/// followed by the octave (e.g. `C-5` for middle C, `F#3`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Pitch(u8);
+
+impl Pitch {
+	/// Construct a Pitch from a raw note index.
+	///
+	/// ### Returns
+	/// The corresponding Pitch, or None if the index
+	/// is outside of the playable note range.
+	pub fn from_note_value(note_val : u8) -> Option<Pitch> {
+		match note_val {
+			NOTE_MIN..=NOTE_MAX => Some(Pitch(note_val)),
+			_ => None,
+		}
+	}
+
+	/// Construct a Pitch from an octave and a semitone.
+	///
+	/// ### Parameters
+	/// * `octave` : The octave, between 0 and 9.
+	/// * `semitone` : The semitone within that octave, between 0 (C) and 11 (B).
+	///
+	/// ### Returns
+	/// The corresponding Pitch, or None if either value is out of range.
+	pub fn from_octave_semitone(octave : u8, semitone : u8) -> Option<Pitch> {
+		if octave > 9 || semitone > 11 {
+			None
+		} else {
+			Some(Pitch(octave * 12 + semitone + NOTE_MIN))
+		}
+	}
+
+	/// Construct a Pitch from a MIDI note number.
+	///
+	/// ### Returns
+	/// The corresponding Pitch, or None if the note is too high
+	/// for OpenMPT (MIDI notes 120 through 127).
+	pub fn from_midi(midi_note : u8) -> Option<Pitch> {
+		Pitch::from_note_value(midi_note.saturating_add(NOTE_MIN))
+	}
+
+	/// Returns the Pitch of a middle C.
+	pub fn middle_c() -> Pitch {
+		Pitch(NOTE_MIDDLEC)
+	}
+
+	/// Returns the raw note index, as stored in pattern data.
+	pub fn value(&self) -> u8 {
+		self.0
+	}
+
+	/// Returns the octave of the note, between 0 and 9.
+	pub fn octave(&self) -> u8 {
+		(self.0 - NOTE_MIN) / 12
+	}
+
+	/// Returns the semitone of the note within its octave, between 0 (C) and 11 (B).
+	pub fn semitone(&self) -> u8 {
+		(self.0 - NOTE_MIN) % 12
+	}
+
+	/// Returns the MIDI note number of the note, where 60 is the middle C.
+	pub fn to_midi(&self) -> u8 {
+		self.0 - NOTE_MIN
+	}
+
+	/// Returns the frequency of the note in Hz, in 12-tone equal temperament tuned to A-5 = 440 Hz.
+	///
+	/// ### Remarks
+	/// This is the nominal pitch of the note. The frequency actually played back
+	/// depends on the sample's own tuning, finetune, tuning mode, etc.
+	pub fn frequency(&self) -> f64 {
+		let semitones_from_a440 = f64::from(self.0) - f64::from(NOTE_A440);
+		440.0 * (semitones_from_a440 / 12.0).exp2()
+	}
+}
+
+impl fmt::Display for Pitch {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}{}", NOTE_NAMES[self.semitone() as usize], self.octave())
+	}
+}
+
+impl FromStr for Pitch {
+	type Err = String;
+
+	/// Parse a note name as displayed by OpenMPT, such as `C-5` or `F#3`.
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		if s.len() != 3 || !s.is_char_boundary(2) {
+			return Err(format!("Invalid note name \"{}\"", s));
+		}
+
+		let (name, octave) = s.split_at(2);
+
+		let semitone = NOTE_NAMES.iter().position(|n| *n == name);
+		let octave = u8::from_str(octave).ok();
+
+		match (semitone, octave) {
+			(Some(semitone), Some(octave)) => Pitch::from_octave_semitone(octave, semitone as u8)
+				.ok_or_else(|| format!("Invalid note name \"{}\"", s)),
+			_ => Err(format!("Invalid note name \"{}\"", s)),
+		}
+	}
+}
+
 /// An enum containing the different value for Volume commands.
 ///
 /// Each variant contains its own volume parameter where applicable.
@@ -182,6 +410,7 @@ pub enum SpecialNote {
 /// use frequencies or periods, etc.
 ///
 /// The libopenmpt developpers **do not recommend** relying on these, **you have been warned**.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum VolumeCommand {
 	None,
 	Volume(u8),
@@ -203,6 +432,8 @@ pub enum VolumeCommand {
 	// Unused
 	DelayCut(u8),
 	Offset(u8),
+	/// A volume command unknown to this crate, with its raw command and parameter
+	Unknown(u8, u8),
 }
 
 /// An enum containing the different value for Effect commands.
@@ -217,6 +448,7 @@ pub enum VolumeCommand {
 /// use frequencies or periods, etc.
 ///
 /// The libopenmpt developpers **do not recommend** relying on these, **you have been warned**.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum EffectCommand {
 	None,
 	/// Cycle between note, note+x and note+y on each tick
@@ -307,4 +539,609 @@ pub enum EffectCommand {
 	// x : chns, y: enable
 	DBMEcho(u8, u8),
 	OffsetPercentage(u8),
-}
\ No newline at end of file
+	/// An effect unknown to this crate, with its raw command and parameter
+	Unknown(u8, u8),
+}
+
+// Effect letters for each format, indexed by raw command, as found in
+// OpenMPT's soundlib/mod_specifications.cpp (extended specifications).
+const EFFECT_LETTERS_MOD:&[u8] = b" 0123456789ABCD?FF?E??????????????????????";
+const EFFECT_LETTERS_XM:&[u8]  = b" 0123456789ABCDRFFTE???GHK?YXPLZ\\?#???????";
+const EFFECT_LETTERS_S3M:&[u8] = b" JFEGHLKRXODB?CQATI?SMNVW?UY?P?Z\\?#???????";
+const EFFECT_LETTERS_IT:&[u8]  = b" JFEGHLKRXODB?CQATI?SMNVW?UY?P?Z\\?#???????";
+const EFFECT_LETTERS_MPT:&[u8] = b" JFEGHLKRXODB?CQATI?SMNVW?UY?P?Z\\:#???????";
+
+/// The module formats whose effect letters are known, which
+/// OpenMPT uses internally to represent all other formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ModuleFormat {
+	/// ProTracker and compatibles
+	Mod,
+	/// Scream Tracker 3
+	S3m,
+	/// FastTracker II
+	Xm,
+	/// Impulse Tracker
+	It,
+	/// OpenMPT
+	Mptm,
+}
+
+impl ModuleFormat {
+	/// Get the module format from the value of `MetadataKey::TypeExt`.
+	///
+	/// ### Returns
+	/// The format whose effect letters are closest to those of the given format
+	/// (e.g. S3M for 669 and ULT, MOD for MTM and OKT), or None if the format is unknown.
+	///
+	/// ### Remarks
+	/// Formats loaded from other trackers only approximate OpenMPT's display,
+	/// which depends on how each loader converted the module.
+	pub fn from_type_ext(type_ext : &str) -> Option<ModuleFormat> {
+		match type_ext {
+			"mod" | "m15" | "stk" | "st26" | "pt36" | "ice" | "wow" | "mtm" | "med" | "okt" | "digi" | "dtm" => Some(ModuleFormat::Mod),
+			"s3m" | "stm" | "stx" | "669" | "ult" | "far" | "ptm" | "plm" | "dsm" | "amf" | "gdm" => Some(ModuleFormat::S3m),
+			"xm" | "dbm" | "dmf" => Some(ModuleFormat::Xm),
+			"it" | "mdl" | "imf" | "j2b" | "mt2" | "ams" | "psm" => Some(ModuleFormat::It),
+			"mptm" => Some(ModuleFormat::Mptm),
+			_ => None,
+		}
+	}
+
+	fn effect_letters(&self) -> &'static [u8] {
+		match *self {
+			ModuleFormat::Mod => EFFECT_LETTERS_MOD,
+			ModuleFormat::S3m => EFFECT_LETTERS_S3M,
+			ModuleFormat::Xm => EFFECT_LETTERS_XM,
+			ModuleFormat::It => EFFECT_LETTERS_IT,
+			ModuleFormat::Mptm => EFFECT_LETTERS_MPT,
+		}
+	}
+}
+
+impl EffectCommand {
+	/// Decode the sub-command of super commands.
+	///
+	/// ### Returns
+	/// The typed sub-command for `ModCmdEX`, `S3MCmdEX` and `XFinePortaUpDown`,
+	/// or None for every other effect.
+	pub fn extended(&self) -> Option<ExtendedCommand> {
+		match *self {
+			EffectCommand::ModCmdEX(x, y) => Some(ExtendedCommand::Mod(ModExtendedCommand::from_nibbles(x, y))),
+			EffectCommand::S3MCmdEX(x, y) => Some(ExtendedCommand::S3M(S3MExtendedCommand::from_nibbles(x, y))),
+			EffectCommand::XFinePortaUpDown(x, y) => Some(ExtendedCommand::XFinePorta(XFinePortaCommand::from_nibbles(x, y))),
+			_ => None,
+		}
+	}
+
+	/// Get the letter used to display the effect in a given format.
+	///
+	/// ### Returns
+	/// The effect letter as displayed by OpenMPT, a space for `EffectCommand::None`
+	/// or `?` if the effect is not supported by that format.
+	pub fn to_effect_letter(&self, format : ModuleFormat) -> char {
+		let (command, _) = ModCommand::effect_to_command_param(self);
+		format.effect_letters().get(command as usize).map_or('?', |&letter| letter as char)
+	}
+
+	/// Get the effect and its parameter as displayed in a given format.
+	///
+	/// ### Returns
+	/// The effect letter followed by the parameter in hexadecimal (e.g. `A0F`
+	/// in XM or `D0F` in IT for a volume slide), or `...` for `EffectCommand::None`.
+	pub fn to_effect_string(&self, format : ModuleFormat) -> String {
+		match *self {
+			EffectCommand::None => "...".to_owned(),
+			_ => {
+				let (_, param) = ModCommand::effect_to_command_param(self);
+				format!("{}{:02X}", self.to_effect_letter(format), param)
+			},
+		}
+	}
+}
+
+/// The sub-command of a super command, for each family of super commands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtendedCommand {
+	/// Sub-command of `EffectCommand::ModCmdEX` (Exy in MOD and XM)
+	Mod(ModExtendedCommand),
+	/// Sub-command of `EffectCommand::S3MCmdEX` (Sxy in S3M and IT)
+	S3M(S3MExtendedCommand),
+	/// Sub-command of `EffectCommand::XFinePortaUpDown` (Xxy in XM)
+	XFinePorta(XFinePortaCommand),
+}
+
+/// An enum containing the sub-commands of `EffectCommand::ModCmdEX` (E-commands).
+///
+/// Each variant contains the y parameter of the effect.
+///
+/// ### Remarks
+/// Same warning as for `EffectCommand`, the exact meaning of each of those
+/// varies depending on the format and tracker last used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModExtendedCommand {
+	/// E0y : Turn the Amiga LED filter on (0) or off (1)
+	SetFilter(u8),
+	/// E1y : Raise pitch by y, on the first tick only
+	FinePortaUp(u8),
+	/// E2y : Lower pitch by y, on the first tick only
+	FinePortaDown(u8),
+	/// E3y : Make tone portamento slide by whole semitones (1) or not (0)
+	GlissandoControl(u8),
+	/// E4y : Set the vibrato waveform
+	VibratoWaveform(u8),
+	/// E5y : Set the finetune of the current note
+	SetFinetune(u8),
+	/// E6y : Set the pattern loop start (0), or loop back to it y times
+	PatternLoop(u8),
+	/// E7y : Set the tremolo waveform
+	TremoloWaveform(u8),
+	/// E8y : Set panning from 0x0 to 0xF
+	SetPanning(u8),
+	/// E9y : Retrigger the note every y ticks
+	Retrig(u8),
+	/// EAy : Raise sample volume by y, on the first tick only
+	FineVolSlideUp(u8),
+	/// EBy : Lower sample volume by y, on the first tick only
+	FineVolSlideDown(u8),
+	/// ECy : Cut the note after y ticks
+	NoteCut(u8),
+	/// EDy : Delay the note for y ticks
+	NoteDelay(u8),
+	/// EEy : Repeat the row y times, without retriggering notes
+	PatternDelay(u8),
+	/// EFy : Invert the sample loop at speed y (ProTracker funk repeat)
+	InvertLoop(u8),
+}
+
+impl ModExtendedCommand {
+	/// Decode a sub-command from the x and y values of `EffectCommand::ModCmdEX`.
+	pub fn from_nibbles(x : u8, y : u8) -> ModExtendedCommand {
+		use self::ModExtendedCommand::*;
+		match x & 0x0F {
+			0x0 => SetFilter(y),
+			0x1 => FinePortaUp(y),
+			0x2 => FinePortaDown(y),
+			0x3 => GlissandoControl(y),
+			0x4 => VibratoWaveform(y),
+			0x5 => SetFinetune(y),
+			0x6 => PatternLoop(y),
+			0x7 => TremoloWaveform(y),
+			0x8 => SetPanning(y),
+			0x9 => Retrig(y),
+			0xA => FineVolSlideUp(y),
+			0xB => FineVolSlideDown(y),
+			0xC => NoteCut(y),
+			0xD => NoteDelay(y),
+			0xE => PatternDelay(y),
+			_ => InvertLoop(y),
+		}
+	}
+
+	/// Encode the sub-command back into the x and y values of `EffectCommand::ModCmdEX`.
+	pub fn to_nibbles(&self) -> (u8, u8) {
+		use self::ModExtendedCommand::*;
+		match *self {
+			SetFilter(y) => (0x0, y),
+			FinePortaUp(y) => (0x1, y),
+			FinePortaDown(y) => (0x2, y),
+			GlissandoControl(y) => (0x3, y),
+			VibratoWaveform(y) => (0x4, y),
+			SetFinetune(y) => (0x5, y),
+			PatternLoop(y) => (0x6, y),
+			TremoloWaveform(y) => (0x7, y),
+			SetPanning(y) => (0x8, y),
+			Retrig(y) => (0x9, y),
+			FineVolSlideUp(y) => (0xA, y),
+			FineVolSlideDown(y) => (0xB, y),
+			NoteCut(y) => (0xC, y),
+			NoteDelay(y) => (0xD, y),
+			PatternDelay(y) => (0xE, y),
+			InvertLoop(y) => (0xF, y),
+		}
+	}
+}
+
+/// An enum containing the sub-commands of `EffectCommand::S3MCmdEX` (S-commands).
+///
+/// Each variant contains the y parameter of the effect.
+///
+/// ### Remarks
+/// Same warning as for `EffectCommand`, the exact meaning of each of those
+/// varies depending on the format and tracker last used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum S3MExtendedCommand {
+	/// S0y : Set filter (S3M only, ignored by most players)
+	SetFilter(u8),
+	/// S1y : Make tone portamento slide by whole semitones (1) or not (0)
+	GlissandoControl(u8),
+	/// S2y : Set the finetune of the current note
+	SetFinetune(u8),
+	/// S3y : Set the vibrato waveform
+	VibratoWaveform(u8),
+	/// S4y : Set the tremolo waveform
+	TremoloWaveform(u8),
+	/// S5y : Set the panbrello waveform
+	PanbrelloWaveform(u8),
+	/// S6y : Extend the row by y ticks
+	FinePatternDelay(u8),
+	/// S7y : Past note actions, new note actions and envelope toggles
+	InstrumentControl(u8),
+	/// S8y : Set panning from 0x0 to 0xF
+	SetPanning(u8),
+	/// S9y : Surround, reverb, sample direction and other playback toggles
+	SoundControl(u8),
+	/// SAy : Set the high byte of the next sample offset (y * 65536)
+	HighOffset(u8),
+	/// SBy : Set the pattern loop start (0), or loop back to it y times
+	PatternLoop(u8),
+	/// SCy : Cut the note after y ticks
+	NoteCut(u8),
+	/// SDy : Delay the note for y ticks
+	NoteDelay(u8),
+	/// SEy : Repeat the row y times, without retriggering notes
+	PatternDelay(u8),
+	/// SFy : Select the active parametered macro
+	SetActiveMacro(u8),
+}
+
+impl S3MExtendedCommand {
+	/// Decode a sub-command from the x and y values of `EffectCommand::S3MCmdEX`.
+	pub fn from_nibbles(x : u8, y : u8) -> S3MExtendedCommand {
+		use self::S3MExtendedCommand::*;
+		match x & 0x0F {
+			0x0 => SetFilter(y),
+			0x1 => GlissandoControl(y),
+			0x2 => SetFinetune(y),
+			0x3 => VibratoWaveform(y),
+			0x4 => TremoloWaveform(y),
+			0x5 => PanbrelloWaveform(y),
+			0x6 => FinePatternDelay(y),
+			0x7 => InstrumentControl(y),
+			0x8 => SetPanning(y),
+			0x9 => SoundControl(y),
+			0xA => HighOffset(y),
+			0xB => PatternLoop(y),
+			0xC => NoteCut(y),
+			0xD => NoteDelay(y),
+			0xE => PatternDelay(y),
+			_ => SetActiveMacro(y),
+		}
+	}
+
+	/// Encode the sub-command back into the x and y values of `EffectCommand::S3MCmdEX`.
+	pub fn to_nibbles(&self) -> (u8, u8) {
+		use self::S3MExtendedCommand::*;
+		match *self {
+			SetFilter(y) => (0x0, y),
+			GlissandoControl(y) => (0x1, y),
+			SetFinetune(y) => (0x2, y),
+			VibratoWaveform(y) => (0x3, y),
+			TremoloWaveform(y) => (0x4, y),
+			PanbrelloWaveform(y) => (0x5, y),
+			FinePatternDelay(y) => (0x6, y),
+			InstrumentControl(y) => (0x7, y),
+			SetPanning(y) => (0x8, y),
+			SoundControl(y) => (0x9, y),
+			HighOffset(y) => (0xA, y),
+			PatternLoop(y) => (0xB, y),
+			NoteCut(y) => (0xC, y),
+			NoteDelay(y) => (0xD, y),
+			PatternDelay(y) => (0xE, y),
+			SetActiveMacro(y) => (0xF, y),
+		}
+	}
+}
+
+/// An enum containing the sub-commands of `EffectCommand::XFinePortaUpDown` (X-commands).
+///
+/// Each variant contains the y parameter of the effect.
+///
+/// ### Remarks
+/// Only X1y and X2y are part of the original XM format, the others
+/// are OpenMPT extensions mirroring their S-command counterparts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XFinePortaCommand {
+	/// X1y : Raise pitch by y/4, on the first tick only
+	ExtraFinePortaUp(u8),
+	/// X2y : Lower pitch by y/4, on the first tick only
+	ExtraFinePortaDown(u8),
+	/// X5y : Set the panbrello waveform
+	PanbrelloWaveform(u8),
+	/// X6y : Extend the row by y ticks
+	FinePatternDelay(u8),
+	/// X9y : Surround, reverb, sample direction and other playback toggles
+	SoundControl(u8),
+	/// XAy : Set the high byte of the next sample offset (y * 65536)
+	HighOffset(u8),
+	/// Any other sub-command, ignored by OpenMPT (x, y)
+	Unused(u8, u8),
+}
+
+impl XFinePortaCommand {
+	/// Decode a sub-command from the x and y values of `EffectCommand::XFinePortaUpDown`.
+	pub fn from_nibbles(x : u8, y : u8) -> XFinePortaCommand {
+		use self::XFinePortaCommand::*;
+		match x & 0x0F {
+			0x1 => ExtraFinePortaUp(y),
+			0x2 => ExtraFinePortaDown(y),
+			0x5 => PanbrelloWaveform(y),
+			0x6 => FinePatternDelay(y),
+			0x9 => SoundControl(y),
+			0xA => HighOffset(y),
+			x => Unused(x, y),
+		}
+	}
+
+	/// Encode the sub-command back into the x and y values of `EffectCommand::XFinePortaUpDown`.
+	pub fn to_nibbles(&self) -> (u8, u8) {
+		use self::XFinePortaCommand::*;
+		match *self {
+			ExtraFinePortaUp(y) => (0x1, y),
+			ExtraFinePortaDown(y) => (0x2, y),
+			PanbrelloWaveform(y) => (0x5, y),
+			FinePatternDelay(y) => (0x6, y),
+			SoundControl(y) => (0x9, y),
+			HighOffset(y) => (0xA, y),
+			Unused(x, y) => (x, y),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn middle_c_is_named_c5() {
+		assert_eq!(Pitch::middle_c().to_string(), "C-5");
+		assert_eq!(Pitch::middle_c().value(), ModCommand::middle_c());
+		assert_eq!(Pitch::middle_c().to_midi(), 60);
+		assert_eq!(Pitch::from_str("F#3").unwrap().to_midi(), 42);
+	}
+
+	#[test]
+	fn a440_is_a5() {
+		let a440 = Pitch::from_str("A-5").unwrap();
+		assert_eq!(a440.to_midi(), 69);
+		assert_eq!(a440.frequency(), 440.0);
+	}
+
+	#[test]
+	fn note_names_round_trip() {
+		for note_val in NOTE_MIN..=NOTE_MAX {
+			let pitch = Pitch::from_note_value(note_val).unwrap();
+			let name = pitch.to_string();
+
+			assert_eq!(name.len(), 3);
+			assert_eq!(Pitch::from_str(&name), Ok(pitch));
+		}
+	}
+
+	#[test]
+	fn octave_and_semitone_round_trip() {
+		for note_val in NOTE_MIN..=NOTE_MAX {
+			let pitch = Pitch::from_note_value(note_val).unwrap();
+
+			assert!(pitch.octave() <= 9);
+			assert!(pitch.semitone() <= 11);
+			assert_eq!(Pitch::from_octave_semitone(pitch.octave(), pitch.semitone()), Some(pitch));
+		}
+	}
+
+	#[test]
+	fn midi_notes_round_trip() {
+		for note_val in NOTE_MIN..=NOTE_MAX {
+			let pitch = Pitch::from_note_value(note_val).unwrap();
+			assert_eq!(Pitch::from_midi(pitch.to_midi()), Some(pitch));
+		}
+
+		assert_eq!(Pitch::from_midi(120), None);
+		assert_eq!(Pitch::from_midi(255), None);
+	}
+
+	#[test]
+	fn frequency_doubles_every_octave() {
+		for note_val in NOTE_MIN..=NOTE_MAX {
+			let pitch = Pitch::from_note_value(note_val).unwrap();
+
+			if let Some(next) = Pitch::from_note_value(note_val + 1) {
+				assert!(next.frequency() > pitch.frequency());
+			}
+
+			if let Some(octave_up) = Pitch::from_note_value(note_val + 12) {
+				let ratio = octave_up.frequency() / pitch.frequency();
+				assert!((ratio - 2.0).abs() < 1e-9);
+			}
+		}
+	}
+
+	#[test]
+	fn out_of_range_notes_are_rejected() {
+		assert_eq!(Pitch::from_note_value(NOTE_NONE), None);
+		assert_eq!(Pitch::from_note_value(NOTE_MAX + 1), None);
+		assert_eq!(Pitch::from_note_value(NOTE_KEYOFF), None);
+		assert_eq!(Pitch::from_octave_semitone(10, 0), None);
+		assert_eq!(Pitch::from_octave_semitone(0, 12), None);
+	}
+
+	#[test]
+	fn invalid_note_names_are_rejected() {
+		for name in &["", "C5", "C-", "H-5", "C-10", "c-5", "C#-", "===", "^^^", "C-é"] {
+			assert!(Pitch::from_str(name).is_err(), "{:?} should not parse", name);
+		}
+	}
+
+	#[test]
+	fn notes_encode_back_to_raw_values() {
+		for note_val in 0..=255u8 {
+			if let Ok(note) = ModCommand::note_from_value(note_val) {
+				assert_eq!(ModCommand::note_to_value(&note), note_val);
+			}
+		}
+	}
+
+	#[test]
+	fn instruments_encode_back_to_raw_values() {
+		for instr in 0..=255u8 {
+			let command = ModCommand::new(0, instr, 0, 0, 0, 0).unwrap();
+			assert_eq!(command.to_raw(), [0, instr, 0, 0, 0, 0]);
+		}
+	}
+
+	#[test]
+	fn volume_commands_encode_back_to_raw_values() {
+		for volcmd in 0..=255u8 {
+			for vol in 0..=255u8 {
+				let decoded = ModCommand::volume_from_command_param(volcmd, vol);
+
+				if volcmd == 0 {
+					// Empty volume commands have no parameter to speak of
+					assert_eq!(ModCommand::volume_to_command_param(&decoded.unwrap()), (0, 0));
+				} else {
+					if volcmd >= 16 {
+						assert_eq!(decoded, Ok(VolumeCommand::Unknown(volcmd, vol)));
+					}
+					assert_eq!(ModCommand::volume_to_command_param(&decoded.unwrap()), (volcmd, vol));
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn effect_commands_encode_back_to_raw_values() {
+		for command in 0..=255u8 {
+			for param in 0..=255u8 {
+				let decoded = ModCommand::effect_from_command_param(command, param);
+
+				if command == 0 {
+					// Empty effect commands have no parameter to speak of
+					assert_eq!(ModCommand::effect_to_command_param(&decoded.unwrap()), (0, 0));
+				} else {
+					if command >= 42 {
+						assert_eq!(decoded, Ok(EffectCommand::Unknown(command, param)));
+					}
+					assert_eq!(ModCommand::effect_to_command_param(&decoded.unwrap()), (command, param));
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn empty_commands_lose_their_parameter() {
+		let decoded = ModCommand::new(NOTE_MIDDLEC, 1, 0, 0, 0x12, 0x34).unwrap();
+		assert_eq!(decoded.volcmd, VolumeCommand::None);
+		assert_eq!(decoded.command, EffectCommand::None);
+		assert_eq!(decoded.to_raw(), [NOTE_MIDDLEC, 1, 0, 0, 0, 0]);
+
+		// Only the parameter of the empty command is dropped
+		let decoded = ModCommand::new(NOTE_MIDDLEC, 1, 1, 0, 0x20, 0x34).unwrap();
+		assert_eq!(decoded.to_raw(), [NOTE_MIDDLEC, 1, 1, 0, 0x20, 0]);
+	}
+
+	#[test]
+	fn nibble_effects_are_repacked() {
+		let command = ModCommand {
+			note: Note::Note(Pitch::middle_c()),
+			instr: 1,
+			volcmd: VolumeCommand::Volume(0x40),
+			command: EffectCommand::VolumeSlide(0xA, 0x0),
+		};
+		assert_eq!(command.to_raw(), [NOTE_MIDDLEC, 1, 1, 11, 0x40, 0xA0]);
+
+		let command = ModCommand { command: EffectCommand::Arpeggio(0x3, 0x7), ..command };
+		assert_eq!(command.to_raw()[3..], [1, 0x40, 0x37]);
+	}
+
+	#[test]
+	fn decoded_commands_encode_back_to_raw_values() {
+		let notes = [NOTE_NONE, NOTE_MIN, NOTE_MIDDLEC, NOTE_MAX, NOTE_KEYOFF, NOTE_NOTECUT, NOTE_FADE, NOTE_PC, NOTE_PCS];
+
+		for &note in &notes {
+			for volcmd in 1..16u8 {
+				for command in 1..42u8 {
+					for &(vol, param) in &[(0x00, 0x00), (0x0F, 0xF0), (0x40, 0x37), (0xFF, 0xFF)] {
+						let raw = [note, 0x2A, volcmd, command, vol, param];
+						let decoded = ModCommand::new(raw[0], raw[1], raw[2], raw[3], raw[4], raw[5]).unwrap();
+						assert_eq!(decoded.to_raw(), raw);
+					}
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn effect_letters_depend_on_format() {
+		let volume_slide = EffectCommand::VolumeSlide(0x0, 0xF);
+		assert_eq!(volume_slide.to_effect_string(ModuleFormat::Mod), "A0F");
+		assert_eq!(volume_slide.to_effect_string(ModuleFormat::Xm), "A0F");
+		assert_eq!(volume_slide.to_effect_string(ModuleFormat::S3m), "D0F");
+		assert_eq!(volume_slide.to_effect_string(ModuleFormat::It), "D0F");
+
+		assert_eq!(EffectCommand::Speed(6).to_effect_string(ModuleFormat::Xm), "F06");
+		assert_eq!(EffectCommand::Tempo(125).to_effect_string(ModuleFormat::Xm), "F7D");
+		assert_eq!(EffectCommand::Speed(6).to_effect_string(ModuleFormat::It), "A06");
+		assert_eq!(EffectCommand::Tempo(125).to_effect_string(ModuleFormat::It), "T7D");
+
+		assert_eq!(EffectCommand::None.to_effect_string(ModuleFormat::It), "...");
+		assert_eq!(EffectCommand::Tremor(1, 1).to_effect_letter(ModuleFormat::Mod), '?');
+		assert_eq!(EffectCommand::Unknown(0x80, 0x12).to_effect_string(ModuleFormat::It), "?12");
+	}
+
+	#[test]
+	fn derived_formats_use_closest_letters() {
+		assert_eq!(ModuleFormat::from_type_ext("669"), Some(ModuleFormat::S3m));
+		assert_eq!(ModuleFormat::from_type_ext("stm"), Some(ModuleFormat::S3m));
+		assert_eq!(ModuleFormat::from_type_ext("mtm"), Some(ModuleFormat::Mod));
+		assert_eq!(ModuleFormat::from_type_ext("okt"), Some(ModuleFormat::Mod));
+		assert_eq!(ModuleFormat::from_type_ext("dbm"), Some(ModuleFormat::Xm));
+		assert_eq!(ModuleFormat::from_type_ext("mdl"), Some(ModuleFormat::It));
+		assert_eq!(ModuleFormat::from_type_ext("wav"), None);
+	}
+
+	#[test]
+	fn every_effect_has_a_letter_in_every_format() {
+		let formats = [ModuleFormat::Mod, ModuleFormat::S3m, ModuleFormat::Xm, ModuleFormat::It, ModuleFormat::Mptm];
+
+		for format in &formats {
+			assert_eq!(format.effect_letters().len(), 42);
+
+			for command in 0..42u8 {
+				let effect = ModCommand::effect_from_command_param(command, 0).unwrap();
+				assert_eq!(effect.to_effect_letter(*format), format.effect_letters()[command as usize] as char);
+			}
+		}
+	}
+
+	#[test]
+	fn extended_commands_round_trip() {
+		for x in 0..16u8 {
+			for y in 0..16u8 {
+				assert_eq!(ModExtendedCommand::from_nibbles(x, y).to_nibbles(), (x, y));
+				assert_eq!(S3MExtendedCommand::from_nibbles(x, y).to_nibbles(), (x, y));
+				assert_eq!(XFinePortaCommand::from_nibbles(x, y).to_nibbles(), (x, y));
+			}
+		}
+	}
+
+	#[test]
+	fn super_commands_decode_to_extended_commands() {
+		let note_delay = ModCommand::effect_from_command_param(19, 0xD3).unwrap();
+		assert_eq!(note_delay.extended(), Some(ExtendedCommand::Mod(ModExtendedCommand::NoteDelay(3))));
+
+		let pattern_loop = ModCommand::effect_from_command_param(20, 0xB2).unwrap();
+		assert_eq!(pattern_loop.extended(), Some(ExtendedCommand::S3M(S3MExtendedCommand::PatternLoop(2))));
+
+		let fine_porta = ModCommand::effect_from_command_param(28, 0x14).unwrap();
+		assert_eq!(fine_porta.extended(), Some(ExtendedCommand::XFinePorta(XFinePortaCommand::ExtraFinePortaUp(4))));
+
+		assert_eq!(EffectCommand::Speed(6).extended(), None);
+	}
+
+	#[test]
+	fn note_values_decode_to_pitches() {
+		for note_val in NOTE_MIN..=NOTE_MAX {
+			assert_eq!(ModCommand::note_from_value(note_val), Ok(Note::Note(Pitch(note_val))));
+		}
+	}
+}
@@ -0,0 +1,226 @@
+//! Definitions for the beat grid, which gives the time of every beat
+//! of the song based on its timing map and the rows-per-beat and
+//! rows-per-measure of its patterns.
+//!
+//! A beat starts on every row that is a multiple of the pattern's rows per beat,
+//! and a downbeat on every row that is a multiple of its rows per measure,
+//! matching the row highlights shown by trackers.
+
+use super::Module;
+use super::timing::TimingMap;
+use std::collections::HashMap;
+use std::os::raw::*;
+
+/// The rows per beat and rows per measure of a pattern.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize))]
+pub struct TimeSignature {
+	pub rows_per_beat: i32,
+	pub rows_per_measure: i32,
+}
+
+impl Default for TimeSignature {
+	/// The OpenMPT defaults, 4 rows per beat and 16 rows per measure.
+	fn default() -> TimeSignature {
+		TimeSignature { rows_per_beat: 4, rows_per_measure: 16 }
+	}
+}
+
+/// A single beat of the song.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize))]
+pub struct Beat {
+	/// Time at which the beat starts, in seconds from the start of the song
+	pub time_seconds: c_double,
+	/// Time at which the beat starts, in frames from the start of the song
+	pub frame: u64,
+	/// Index of the beat in the song, starting at 0
+	pub beat_index: u64,
+	/// Whether the beat starts a measure
+	pub is_downbeat: bool,
+	/// Tempo from this beat until the next one, in beats per minute
+	pub bpm: c_double,
+	pub order: i32,
+	pub row: i32,
+}
+
+/// Every beat of a song, obtained with `Module::beat_grid`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BeatGrid {
+	/// The beats of the song, in playback order.
+	pub beats: Vec<Beat>,
+}
+
+impl BeatGrid {
+	/// Build the beat grid of a song.
+	///
+	/// ### Parameters
+	/// * `map` : The timing map of the song.
+	/// * `signature_of` : Gives the time signature of a pattern, by pattern number.
+	///
+	/// ### Returns
+	/// The beat grid. Rows are only beats if their own pattern says so,
+	/// so pattern breaks and jumps can make some beats shorter than others.
+	/// A row played several times in place (with a pattern delay, for example)
+	/// counts as a single row, making its beat longer.
+	pub fn from_timing_map<F: FnMut(i32) -> TimeSignature>(map: &TimingMap, mut signature_of: F) -> BeatGrid {
+		let sample_rate = c_double::from(map.sample_rate);
+
+		// Rows played again in place (by pattern delays) are a single row
+		let runs = map.row_runs();
+
+		// Index in runs of every beat, along with whether it is a downbeat
+		let beat_rows: Vec<(usize, bool)> = runs.iter().enumerate().filter_map(|(index, run)| {
+			let timing = &map.rows[run.start];
+			let signature = signature_of(timing.pattern);
+			let rows_per_beat = signature.rows_per_beat.max(1);
+			let rows_per_measure = signature.rows_per_measure.max(1);
+
+			if timing.row % rows_per_beat == 0 {
+				Some((index, timing.row % rows_per_measure == 0))
+			} else {
+				None
+			}
+		}).collect();
+
+		let beats = beat_rows.iter().enumerate().map(|(beat_index, &(index, is_downbeat))| {
+			let timing = &map.rows[runs[index].start];
+			let end_index = beat_rows.get(beat_index + 1).map_or(runs.len(), |&(next_index, _)| next_index);
+			let end_frame = map.entry_frame(runs.get(end_index).map_or(map.rows.len(), |run| run.start));
+
+			// Measured over the rows of the beat, so tempo slides and swing average out
+			let rows_per_beat = c_double::from(signature_of(timing.pattern).rows_per_beat.max(1));
+			let beat_fraction = (end_index - index) as c_double / rows_per_beat;
+			let seconds = end_frame.saturating_sub(timing.frame) as c_double / sample_rate;
+			let bpm = if seconds > 0.0 { 60.0 * beat_fraction / seconds } else { 0.0 };
+
+			Beat {
+				time_seconds: timing.time_seconds,
+				frame: timing.frame,
+				beat_index: beat_index as u64,
+				is_downbeat,
+				bpm,
+				order: timing.order,
+				row: timing.row,
+			}
+		}).collect();
+
+		BeatGrid { beats }
+	}
+
+	/// Get the beats as `(time_seconds, beat_index, is_downbeat)`.
+	pub fn to_tuples(&self) -> Vec<(c_double, u64, bool)> {
+		self.beats.iter().map(|beat| (beat.time_seconds, beat.beat_index, beat.is_downbeat)).collect()
+	}
+
+	/// Get the beat playing at a given time.
+	///
+	/// ### Returns
+	/// The last beat starting at or before `seconds`, or None if there is none.
+	pub fn beat_at_seconds(&self, seconds: c_double) -> Option<&Beat> {
+		let next = self.beats.partition_point(|beat| beat.time_seconds <= seconds);
+		if next == 0 { None } else { self.beats.get(next - 1) }
+	}
+}
+
+impl Module {
+	/// Get the time signature of a pattern.
+	///
+	/// ### Parameters
+	/// * `pattern_num` : The index of the pattern.
+	///
+	/// ### Returns
+	/// The rows per beat and rows per measure of that pattern, or the OpenMPT
+	/// defaults if the pattern doesn't exist, the crate was built without the
+	/// `libopenmpt-0-8` feature or the linked libopenmpt is older than 0.8.
+	pub fn get_pattern_time_signature(&mut self, pattern_num: i32) -> TimeSignature {
+		let default = TimeSignature::default();
+
+		match self.get_pattern_by_number(pattern_num) {
+			Some(mut pattern) => TimeSignature {
+				rows_per_beat: pattern.get_rows_per_beat().unwrap_or(default.rows_per_beat),
+				rows_per_measure: pattern.get_rows_per_measure().unwrap_or(default.rows_per_measure),
+			},
+			None => default,
+		}
+	}
+
+	/// Compute the beat grid of the song.
+	///
+	/// ### Parameters
+	/// * `map` : The timing map of the song, as returned by `timing_map`.
+	///
+	/// ### Returns
+	/// Every beat of the song, using the time signature of each pattern.
+	pub fn beat_grid(&mut self, map: &TimingMap) -> BeatGrid {
+		let mut signatures = HashMap::new();
+
+		BeatGrid::from_timing_map(map, |pattern_num| {
+			*signatures.entry(pattern_num).or_insert_with(|| self.get_pattern_time_signature(pattern_num))
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::test_helper;
+
+	// 100 frames per row, patterns of 8 rows
+	fn test_map(num_rows: i32) -> TimingMap {
+		let positions: Vec<(i32, i32)> = (0..num_rows).map(|index| (index / 8, index % 8)).collect();
+		test_helper::build_timing_map(&positions, 100)
+	}
+
+	#[test]
+	fn beats_follow_time_signature() {
+		let map = test_map(16);
+		let grid = BeatGrid::from_timing_map(&map, |_| TimeSignature { rows_per_beat: 2, rows_per_measure: 8 });
+
+		assert_eq!(grid.beats.len(), 8);
+		assert_eq!(grid.to_tuples()[..3], [(0.0, 0, true), (0.2, 1, false), (0.4, 2, false)]);
+		assert!(grid.beats[4].is_downbeat);
+		assert_eq!((grid.beats[4].order, grid.beats[4].row), (1, 0));
+		assert!(grid.beats.iter().all(|beat| (beat.bpm - 300.0).abs() < 1e-9));
+	}
+
+	#[test]
+	fn beats_can_be_shortened() {
+		// The second pattern is cut short after 3 rows
+		let map = test_map(11);
+		let grid = BeatGrid::from_timing_map(&map, |pattern| {
+			TimeSignature { rows_per_beat: if pattern == 0 { 4 } else { 2 }, rows_per_measure: 8 }
+		});
+
+		let rows: Vec<i32> = grid.beats.iter().map(|beat| beat.row).collect();
+		assert_eq!(rows, vec![0, 4, 0, 2]);
+		assert!((grid.beats[3].bpm - 300.0).abs() < 1e-9);
+		assert_eq!(grid.beat_at_seconds(0.5).unwrap().beat_index, 1);
+		assert_eq!(grid.beat_at_seconds(-0.5), None);
+	}
+
+	#[test]
+	fn delayed_rows_are_one_row() {
+		// The second row is played 3 times by a pattern delay
+		let positions = [(0, 0), (0, 1), (0, 1), (0, 1), (0, 2), (0, 3), (0, 4), (0, 5)];
+		let map = test_helper::build_timing_map(&positions, 100);
+		let grid = BeatGrid::from_timing_map(&map, |_| TimeSignature { rows_per_beat: 2, rows_per_measure: 8 });
+
+		let rows: Vec<(i32, u64)> = grid.beats.iter().map(|beat| (beat.row, beat.frame)).collect();
+		assert_eq!(rows, vec![(0, 0), (2, 400), (4, 600)]);
+		assert!((grid.beats[0].bpm - 150.0).abs() < 1e-9);
+		assert!((grid.beats[1].bpm - 300.0).abs() < 1e-9);
+	}
+
+	#[test]
+	fn beat_grid_covers_song() {
+		let mut module = test_helper::load_file_as_module("UNATCO.it").unwrap();
+		let map = module.timing_map(48000);
+		let grid = module.beat_grid(&map);
+
+		assert_eq!(grid.beats[0].frame, 0);
+		assert!(grid.beats[0].is_downbeat);
+		assert!(grid.beats.iter().all(|beat| beat.bpm > 0.0));
+		assert!(grid.beats.windows(2).all(|pair| pair[0].frame <= pair[1].frame));
+	}
+}
@@ -1,8 +1,16 @@
 //! Definitions for all types and methods used to set and query
 //! libopenmpt parameters for the loaded module
+//!
+//! ## Remarks
+//! libopenmpt silently ignores most invalid ctl values, so the typed setters
+//! check the value against its documented range, and read it back afterwards
+//! to make sure it was applied.
 
 use openmpt_sys;
 use super::Module;
+use error::Error;
+use ffi;
+use std::fmt;
 use std::str::FromStr;
 use std::os::raw::*;
 
@@ -14,6 +22,28 @@ const SEEK_SYNC_SAMPLES:&str = "seek.sync_samples";
 const PLAY_TEMPO_FACTOR:&str = "play.tempo_factor";
 const PLAY_PITCH_FACTOR:&str = "play.pitch_factor";
 const DITHER:&str = "dither";
+const SUBSONG:&str = "subsong";
+const PLAY_AT_END:&str = "play.at_end";
+const RENDER_RESAMPLER_EMULATE_AMIGA:&str = "render.resampler.emulate_amiga";
+const RENDER_RESAMPLER_EMULATE_AMIGA_TYPE:&str = "render.resampler.emulate_amiga_type";
+const RENDER_OPL_VOLUME_FACTOR:&str = "render.opl.volume_factor";
+
+// Value types of the ctls documented by libopenmpt
+const KNOWN_CTLS:[(&str, CtlType); 13] = [
+	(LOAD_SKIP_SAMPLES, CtlType::Boolean),
+	(LOAD_SKIP_PATTERNS, CtlType::Boolean),
+	(LOAD_SKIP_PLUGINS, CtlType::Boolean),
+	(LOAD_SKIP_SUBSONGS_INIT, CtlType::Boolean),
+	(SEEK_SYNC_SAMPLES, CtlType::Boolean),
+	(SUBSONG, CtlType::Integer),
+	(PLAY_TEMPO_FACTOR, CtlType::FloatingPoint),
+	(PLAY_PITCH_FACTOR, CtlType::FloatingPoint),
+	(PLAY_AT_END, CtlType::Text),
+	(RENDER_RESAMPLER_EMULATE_AMIGA, CtlType::Boolean),
+	(RENDER_RESAMPLER_EMULATE_AMIGA_TYPE, CtlType::Text),
+	(RENDER_OPL_VOLUME_FACTOR, CtlType::FloatingPoint),
+	(DITHER, CtlType::Integer),
+];
 
 #[derive(PartialEq, Debug)]
 pub enum DitherMode {
@@ -41,6 +71,81 @@ impl FromStr for DitherMode {
 	}
 }
 
+/// What to do when the end of the song is reached.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum AtEndBehavior {
+	/// Fade the module out for a short while (default).
+	/// Subsequent reads after the fadeout render nothing.
+	Fadeout,
+	/// Stop rendering when the end is reached.
+	/// Subsequent reads continue playing from the loop start.
+	Continue,
+	/// Stop rendering when the end is reached.
+	/// Subsequent reads render nothing.
+	Stop,
+}
+
+impl AtEndBehavior {
+	fn to_str(self) -> &'static str {
+		match self {
+			AtEndBehavior::Fadeout => "fadeout",
+			AtEndBehavior::Continue => "continue",
+			AtEndBehavior::Stop => "stop",
+		}
+	}
+}
+
+impl FromStr for AtEndBehavior {
+	type Err = &'static str;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"fadeout" => Ok(AtEndBehavior::Fadeout),
+			"continue" => Ok(AtEndBehavior::Continue),
+			"stop" => Ok(AtEndBehavior::Stop),
+			_ => Err("Failed to parse return value as known end of song behavior")
+		}
+	}
+}
+
+/// Amiga filter to emulate when `render.resampler.emulate_amiga` is enabled.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum AmigaFilterType {
+	/// Chosen by libopenmpt depending on the module (default).
+	Auto,
+	/// Amiga 500 filter.
+	A500,
+	/// Amiga 1200 filter.
+	A1200,
+	/// BLEP synthesis without filtering.
+	Unfiltered,
+}
+
+impl AmigaFilterType {
+	fn to_str(self) -> &'static str {
+		match self {
+			AmigaFilterType::Auto => "auto",
+			AmigaFilterType::A500 => "a500",
+			AmigaFilterType::A1200 => "a1200",
+			AmigaFilterType::Unfiltered => "unfiltered",
+		}
+	}
+}
+
+impl FromStr for AmigaFilterType {
+	type Err = &'static str;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"auto" => Ok(AmigaFilterType::Auto),
+			"a500" => Ok(AmigaFilterType::A500),
+			"a1200" => Ok(AmigaFilterType::A1200),
+			"unfiltered" => Ok(AmigaFilterType::Unfiltered),
+			_ => Err("Failed to parse return value as known Amiga filter type")
+		}
+	}
+}
+
 /// Ctls to use with `create`, `create_from_memory`, and
 /// `could_open_propability` in lists of initial ctls.
 pub enum Ctl {
@@ -63,10 +168,17 @@ pub enum Ctl {
 	PlaybackPitchFactor(c_double),
 	/// Set the dither algorithm that is used for the 16 bit versions of the rendering methods.
 	DitherMode16Bit(DitherMode),
+	/// Set to `true` to emulate the Amiga's (Paula) sound chip when playing
+	/// Amiga modules, instead of using the interpolation filter.
+	EmulateAmigaResampler(bool),
+	/// Set the Amiga filter to emulate along with `EmulateAmigaResampler`.
+	AmigaResamplerFilter(AmigaFilterType),
+	/// Set what happens when the end of the song is reached.
+	AtEnd(AtEndBehavior),
 }
 
 impl Ctl {
-	fn key_to_str(&self) -> String {
+	pub(super) fn key_to_str(&self) -> String {
 		match *self {
 			Ctl::SkipLoadingSamples(_) =>  LOAD_SKIP_SAMPLES,
 			Ctl::SkipLoadingPatterns(_) => LOAD_SKIP_PATTERNS,
@@ -76,10 +188,13 @@ impl Ctl {
 			Ctl::PlaybackTempoFactor(_) => PLAY_TEMPO_FACTOR,
 			Ctl::PlaybackPitchFactor(_) => PLAY_PITCH_FACTOR,
 			Ctl::DitherMode16Bit(_) => DITHER,
+			Ctl::EmulateAmigaResampler(_) => RENDER_RESAMPLER_EMULATE_AMIGA,
+			Ctl::AmigaResamplerFilter(_) => RENDER_RESAMPLER_EMULATE_AMIGA_TYPE,
+			Ctl::AtEnd(_) => PLAY_AT_END,
 		}.to_owned()
 	}
 
-	fn param_to_str(&self) -> String {
+	pub(super) fn param_to_str(&self) -> String {
 		use self::Ctl::*;
 		match *self {
 			SkipLoadingSamples(ref param) =>  if *param {"1"} else {"0"}.to_owned(),
@@ -95,10 +210,166 @@ impl Ctl {
 				DitherMode::ModPlug => "2",
 				DitherMode::Simple => "3",
 			}.to_owned(),
+			EmulateAmigaResampler(ref param) => if *param {"1"} else {"0"}.to_owned(),
+			AmigaResamplerFilter(ref param) => param.to_str().to_owned(),
+			AtEnd(ref param) => param.to_str().to_owned(),
+		}
+	}
+
+	/// Check the parameter against the range documented by libopenmpt.
+	///
+	/// ### Returns
+	/// An error describing the problem if the value is out of range.
+	pub fn validate(&self) -> Result<(), Error> {
+		match *self {
+			Ctl::PlaybackTempoFactor(param) | Ctl::PlaybackPitchFactor(param) => check_factor(&self.key_to_str(), param),
+			_ => Ok(()),
+		}
+	}
+
+	// Whether the value read back from libopenmpt is the one that was set
+	fn matches(&self, str_val: &str) -> bool {
+		use self::Ctl::*;
+		match *self {
+			SkipLoadingSamples(param) | SkipLoadingPatterns(param) | SkipLoadingPlugins(param) |
+			SkipSubsongPreinit(param) | SyncSamplesWhenSeeking(param) | EmulateAmigaResampler(param) =>
+				i32::from_str(str_val).map(|num| (num != 0) == param).unwrap_or(false),
+			PlaybackTempoFactor(param) | PlaybackPitchFactor(param) =>
+				c_double::from_str(str_val).map(|num| factors_match(num, param)).unwrap_or(false),
+			DitherMode16Bit(ref param) => DitherMode::from_str(str_val).map(|mode| mode == *param).unwrap_or(false),
+			AmigaResamplerFilter(param) => AmigaFilterType::from_str(str_val) == Ok(param),
+			AtEnd(param) => AtEndBehavior::from_str(str_val) == Ok(param),
+		}
+	}
+}
+
+fn check_factor(key: &str, factor: c_double) -> Result<(), Error> {
+	if factor.is_finite() && factor > 0.0 {
+		Ok(())
+	} else {
+		Err(Error::InvalidCtl {
+			key: key.to_owned(),
+			reason: format!("factor must be a positive number, got {}", factor),
+		})
+	}
+}
+
+// libopenmpt may format floating point ctls with less precision than they are stored with
+fn factors_match(read_back: c_double, expected: c_double) -> bool {
+	(read_back - expected).abs() <= 1e-5 * expected.abs().max(1.0)
+}
+
+/// The type of value a ctl holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CtlType {
+	Boolean,
+	Integer,
+	FloatingPoint,
+	Text,
+}
+
+impl CtlType {
+	/// Get the type of a ctl documented by libopenmpt.
+	///
+	/// ### Returns
+	/// The type of the ctl, or None if the ctl is unknown to this crate.
+	pub fn of_known_ctl(key: &str) -> Option<CtlType> {
+		KNOWN_CTLS.iter()
+			.find(|&&(known_key, _)| known_key == key)
+			.map(|&(_, ctl_type)| ctl_type)
+	}
+}
+
+impl fmt::Display for CtlType {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(match *self {
+			CtlType::Boolean => "boolean",
+			CtlType::Integer => "integer",
+			CtlType::FloatingPoint => "floating point",
+			CtlType::Text => "text",
+		})
+	}
+}
+
+/// A ctl value, along with its type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CtlValue {
+	Boolean(bool),
+	Integer(i64),
+	FloatingPoint(c_double),
+	Text(String),
+}
+
+impl CtlValue {
+	/// Get the type of this value.
+	pub fn ctl_type(&self) -> CtlType {
+		match *self {
+			CtlValue::Boolean(_) => CtlType::Boolean,
+			CtlValue::Integer(_) => CtlType::Integer,
+			CtlValue::FloatingPoint(_) => CtlType::FloatingPoint,
+			CtlValue::Text(_) => CtlType::Text,
+		}
+	}
+
+	/// Parse a value from its text form, as given to `ctl_set`.
+	///
+	/// ### Returns
+	/// The value, or None if the text is not a valid value of that type.
+	/// Booleans can be given as `0`, `1`, `false` or `true`.
+	pub fn parse(ctl_type: CtlType, text: &str) -> Option<CtlValue> {
+		match ctl_type {
+			CtlType::Boolean => match text {
+				"0" | "false" => Some(CtlValue::Boolean(false)),
+				"1" | "true" => Some(CtlValue::Boolean(true)),
+				_ => None,
+			},
+			CtlType::Integer => text.parse().ok().map(CtlValue::Integer),
+			CtlType::FloatingPoint => text.parse().ok().map(CtlValue::FloatingPoint),
+			CtlType::Text => Some(CtlValue::Text(text.to_owned())),
 		}
 	}
 }
 
+impl fmt::Display for CtlValue {
+	/// Format the value the way `ctl_set` expects it.
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			CtlValue::Boolean(value) => f.write_str(if value {"1"} else {"0"}),
+			CtlValue::Integer(value) => write!(f, "{}", value),
+			CtlValue::FloatingPoint(value) => write!(f, "{}", value),
+			CtlValue::Text(ref value) => f.write_str(value),
+		}
+	}
+}
+
+/// A ctl supported by the loaded module, obtained with `Module::ctl_infos`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CtlInfo {
+	/// The ctl key (e.g. `play.tempo_factor`)
+	pub key: String,
+	/// The type of the ctl, or None if it is unknown to this crate.
+	///
+	/// The value of unknown ctls can still be queried as text.
+	pub ctl_type: Option<CtlType>,
+}
+
+impl CtlInfo {
+	/// Get the type to use when querying the ctl, `Text` if unknown.
+	pub fn value_type(&self) -> CtlType {
+		self.ctl_type.unwrap_or(CtlType::Text)
+	}
+}
+
+fn parse_ctl_list(ctls: &str) -> Vec<CtlInfo> {
+	ctls.split(';')
+		.filter(|key| !key.is_empty())
+		.map(|key| CtlInfo {
+			key: key.to_owned(),
+			ctl_type: CtlType::of_known_ctl(key),
+		})
+		.collect()
+}
+
 impl Module {
 	/// Get whether or not to avoid loading samples into memory.
 	pub fn ctl_get_load_skip_samples(&mut self) -> Option<bool> {
@@ -112,8 +383,8 @@ impl Module {
 	}
 
 	/// Set whether or not to avoid loading samples into memory.
-	pub fn ctl_set_load_skip_samples(&mut self, value: bool) -> bool {
-		self.enum_ctl_set(&Ctl::SkipLoadingSamples(value))
+	pub fn ctl_set_load_skip_samples(&mut self, value: bool) -> Result<(), Error> {
+		self.validated_ctl_set(&Ctl::SkipLoadingSamples(value))
 	}
 
 	/// Get whether or not to avoid loading patterns into memory.
@@ -128,8 +399,8 @@ impl Module {
 	}
 
 	/// Set whether or not to avoid loading patterns into memory.
-	pub fn ctl_set_load_skip_patterns(&mut self, value: bool) -> bool {
-		self.enum_ctl_set(&Ctl::SkipLoadingPatterns(value))
+	pub fn ctl_set_load_skip_patterns(&mut self, value: bool) -> Result<(), Error> {
+		self.validated_ctl_set(&Ctl::SkipLoadingPatterns(value))
 	}
 
 	/// Get whether or not to avoid loading plugins.
@@ -144,8 +415,8 @@ impl Module {
 	}
 
 	/// Set whether or not to avoid loading plugins.
-	pub fn ctl_set_load_skip_plugins(&mut self, value: bool) -> bool {
-		self.enum_ctl_set(&Ctl::SkipLoadingPlugins(value))
+	pub fn ctl_set_load_skip_plugins(&mut self, value: bool) -> Result<(), Error> {
+		self.validated_ctl_set(&Ctl::SkipLoadingPlugins(value))
 	}
 
 	/// Get whether or not to avoid pre-initializing sub-songs.
@@ -160,8 +431,8 @@ impl Module {
 	}
 
 	/// Set whether or not to avoid pre-initializing sub-songs.
-	pub fn ctl_set_load_skip_subsongs_init(&mut self, value: bool) -> bool {
-		self.enum_ctl_set(&Ctl::SkipSubsongPreinit(value))
+	pub fn ctl_set_load_skip_subsongs_init(&mut self, value: bool) -> Result<(), Error> {
+		self.validated_ctl_set(&Ctl::SkipSubsongPreinit(value))
 	}
 
 	/// Get whether or not to sync sample playback when seeking.
@@ -176,8 +447,8 @@ impl Module {
 	}
 
 	/// Set whether or not to sync sample playback when seeking.
-	pub fn ctl_set_seek_sync_samples(&mut self, value: bool) -> bool {
-		self.enum_ctl_set(&Ctl::SyncSamplesWhenSeeking(value))
+	pub fn ctl_set_seek_sync_samples(&mut self, value: bool) -> Result<(), Error> {
+		self.validated_ctl_set(&Ctl::SyncSamplesWhenSeeking(value))
 	}
 
 	/// Get the floating point tempo factor.
@@ -192,8 +463,8 @@ impl Module {
 	}
 
 	/// Set a floating point tempo factor.
-	pub fn ctl_set_play_tempo_factor(&mut self, value: c_double) -> bool {
-		self.enum_ctl_set(&Ctl::PlaybackTempoFactor(value))
+	pub fn ctl_set_play_tempo_factor(&mut self, value: c_double) -> Result<(), Error> {
+		self.validated_ctl_set(&Ctl::PlaybackTempoFactor(value))
 	}
 
 	/// Get the floating point pitch factor.
@@ -208,8 +479,8 @@ impl Module {
 	}
 
 	/// Set a floating point pitch factor
-	pub fn ctl_set_play_pitch_factor(&mut self, value: c_double) -> bool {
-		self.enum_ctl_set(&Ctl::PlaybackPitchFactor(value))
+	pub fn ctl_set_play_pitch_factor(&mut self, value: c_double) -> Result<(), Error> {
+		self.validated_ctl_set(&Ctl::PlaybackPitchFactor(value))
 	}
 
 	/// Get the dither algorithm that is used for the 16 bit versions of the rendering methods.
@@ -224,8 +495,56 @@ impl Module {
 	}
 
 	/// Set the dither algorithm that is used for the 16 bit versions of the rendering methods.
-	pub fn ctl_set_dither(&mut self, value: DitherMode) -> bool {
-		self.enum_ctl_set(&Ctl::DitherMode16Bit(value))
+	pub fn ctl_set_dither(&mut self, value: DitherMode) -> Result<(), Error> {
+		self.validated_ctl_set(&Ctl::DitherMode16Bit(value))
+	}
+
+	/// Get whether or not the Amiga sound chip is emulated when playing Amiga modules.
+	pub fn ctl_get_render_resampler_emulate_amiga(&mut self) -> Option<bool> {
+		let return_val = self.ctl_get(RENDER_RESAMPLER_EMULATE_AMIGA);
+
+		if let Some(ref str_val) = return_val {
+			i32::from_str(str_val).map(|num| num != 0).ok()
+		} else {
+			None
+		}
+	}
+
+	/// Set whether or not to emulate the Amiga sound chip when playing Amiga modules.
+	pub fn ctl_set_render_resampler_emulate_amiga(&mut self, value: bool) -> Result<(), Error> {
+		self.validated_ctl_set(&Ctl::EmulateAmigaResampler(value))
+	}
+
+	/// Get the Amiga filter that is emulated along with the Amiga sound chip.
+	pub fn ctl_get_render_resampler_emulate_amiga_type(&mut self) -> Option<AmigaFilterType> {
+		let return_val = self.ctl_get(RENDER_RESAMPLER_EMULATE_AMIGA_TYPE);
+
+		if let Some(ref str_val) = return_val {
+			AmigaFilterType::from_str(str_val).ok()
+		} else {
+			None
+		}
+	}
+
+	/// Set the Amiga filter to emulate along with the Amiga sound chip.
+	pub fn ctl_set_render_resampler_emulate_amiga_type(&mut self, value: AmigaFilterType) -> Result<(), Error> {
+		self.validated_ctl_set(&Ctl::AmigaResamplerFilter(value))
+	}
+
+	/// Get what happens when the end of the song is reached.
+	pub fn ctl_get_play_at_end(&mut self) -> Option<AtEndBehavior> {
+		let return_val = self.ctl_get(PLAY_AT_END);
+
+		if let Some(ref str_val) = return_val {
+			AtEndBehavior::from_str(str_val).ok()
+		} else {
+			None
+		}
+	}
+
+	/// Set what happens when the end of the song is reached.
+	pub fn ctl_set_play_at_end(&mut self, value: AtEndBehavior) -> Result<(), Error> {
+		self.validated_ctl_set(&Ctl::AtEnd(value))
 	}
 
 	/// Get ctl value directly, as a string.
@@ -241,7 +560,29 @@ impl Module {
 		})
 	}
 
-	pub(super) fn enum_ctl_set(&mut self, ctl: &Ctl) -> bool {
+	// Set a ctl, then read it back to make sure libopenmpt applied the value
+	pub(super) fn validated_ctl_set(&mut self, ctl: &Ctl) -> Result<(), Error> {
+		ctl.validate()?;
+
+		let key = ctl.key_to_str();
+		if !self.enum_ctl_set(ctl) {
+			return Err(Error::InvalidCtl {
+				reason: format!("libopenmpt rejected value {}", ctl.param_to_str()),
+				key,
+			});
+		}
+
+		match self.ctl_get(&key) {
+			None => Err(Error::UnknownCtl(key)),
+			Some(ref str_val) if ctl.matches(str_val) => Ok(()),
+			Some(str_val) => Err(Error::InvalidCtl {
+				reason: format!("value {} was not applied, ctl is still {}", ctl.param_to_str(), str_val),
+				key,
+			}),
+		}
+	}
+
+	fn enum_ctl_set(&mut self, ctl: &Ctl) -> bool {
 		let key = ctl.key_to_str();
 		let val = ctl.param_to_str();
 
@@ -268,12 +609,207 @@ impl Module {
 	///
 	/// ### Returns
 	/// A semicolon-separated list containing all supported ctl keys.
-	pub fn get_ctls(&mut self) -> String {
+	pub fn get_ctls(&mut self) -> Result<String, Error> {
 		let opt_string = get_string! {
 			openmpt_sys::openmpt_module_get_ctls(self.inner)
 		};
 
-		opt_string.expect("Got null pointer instead of string")
+		opt_string.ok_or(Error::NullString("ctl list"))
+	}
+
+	/// Retrieve supported ctls, along with their types.
+	///
+	/// ### Returns
+	/// All ctls supported by the loaded module and the libopenmpt version in use.
+	pub fn ctl_infos(&mut self) -> Result<Vec<CtlInfo>, Error> {
+		Ok(parse_ctl_list(&self.get_ctls()?))
+	}
+
+	/// Get a ctl value, using the type of the ctl.
+	///
+	/// ### Parameters
+	/// * `key` : The ctl key whose value should be retrieved.
+	///
+	/// ### Returns
+	/// The ctl value, or its text form if its type is unknown to this crate.
+	pub fn ctl_get_value(&mut self, key: &str) -> Result<CtlValue, Error> {
+		let ctl_type = self.check_ctl(key, None)?;
+
+		match ctl_type {
+			CtlType::Boolean => self.ctl_get_boolean(key).map(CtlValue::Boolean),
+			CtlType::Integer => self.ctl_get_integer(key).map(CtlValue::Integer),
+			CtlType::FloatingPoint => self.ctl_get_floatingpoint(key).map(CtlValue::FloatingPoint),
+			CtlType::Text => self.ctl_get_text(key).map(CtlValue::Text),
+		}
+	}
+
+	/// Get the value of a boolean ctl.
+	///
+	/// ### Parameters
+	/// * `key` : The ctl key whose value should be retrieved.
+	///
+	/// ### Returns
+	/// The ctl value, or an error if libopenmpt fails to read it.
+	pub fn ctl_get_boolean(&mut self, key: &str) -> Result<bool, Error> {
+		self.check_ctl(key, Some(CtlType::Boolean))?;
+
+		let inner = self.inner;
+		self.typed_ctl_get(key, || with_string!(key, {
+			ffi::openmpt_module_ctl_get_boolean(inner, key)
+		})).map(|value| value != 0)
+	}
+
+	/// Get the value of an integer ctl.
+	///
+	/// ### Parameters
+	/// * `key` : The ctl key whose value should be retrieved.
+	///
+	/// ### Returns
+	/// The ctl value, or an error if libopenmpt fails to read it.
+	pub fn ctl_get_integer(&mut self, key: &str) -> Result<i64, Error> {
+		self.check_ctl(key, Some(CtlType::Integer))?;
+
+		let inner = self.inner;
+		self.typed_ctl_get(key, || with_string!(key, {
+			ffi::openmpt_module_ctl_get_integer(inner, key)
+		}))
+	}
+
+	/// Get the value of a floating point ctl.
+	///
+	/// ### Parameters
+	/// * `key` : The ctl key whose value should be retrieved.
+	///
+	/// ### Returns
+	/// The ctl value, or an error if libopenmpt fails to read it.
+	pub fn ctl_get_floatingpoint(&mut self, key: &str) -> Result<c_double, Error> {
+		self.check_ctl(key, Some(CtlType::FloatingPoint))?;
+
+		let inner = self.inner;
+		self.typed_ctl_get(key, || with_string!(key, {
+			ffi::openmpt_module_ctl_get_floatingpoint(inner, key)
+		}))
+	}
+
+	// The typed getters return 0 on failure, which is also a valid value,
+	// so failures can only be told apart through the module error state.
+	fn typed_ctl_get<T, F: FnOnce() -> T>(&mut self, key: &str, getter: F) -> Result<T, Error> {
+		let value = unsafe {
+			ffi::openmpt_module_error_clear(self.inner);
+			let value = getter();
+
+			if ffi::openmpt_module_error_get_last(self.inner) != ffi::ERROR_OK {
+				ffi::openmpt_module_error_clear(self.inner);
+				None
+			} else {
+				Some(value)
+			}
+		};
+
+		value.ok_or_else(|| Error::InvalidCtl {
+			key: key.to_owned(),
+			reason: "libopenmpt failed to read the value".to_owned(),
+		})
+	}
+
+	/// Get the value of any ctl, as text.
+	///
+	/// ### Parameters
+	/// * `key` : The ctl key whose value should be retrieved.
+	pub fn ctl_get_text(&mut self, key: &str) -> Result<String, Error> {
+		self.check_ctl(key, None)?;
+
+		self.ctl_get(key).ok_or(Error::NullString("ctl value"))
+	}
+
+	/// Set a ctl value, using the typed setter matching the value.
+	///
+	/// ### Parameters
+	/// * `key` : The ctl key whose value should be set.
+	/// * `value` : The value that should be set. Text values are accepted for every ctl,
+	///   and parsed into the type of the ctl if it is known (see `CtlValue::parse`).
+	///
+	/// ### Returns
+	/// An error if the ctl doesn't exist or doesn't hold that type of value,
+	/// or if libopenmpt rejected the value or did not apply it.
+	pub fn ctl_set_value(&mut self, key: &str, value: &CtlValue) -> Result<(), Error> {
+		if let CtlValue::Text(ref text) = *value {
+			match CtlType::of_known_ctl(key) {
+				Some(ctl_type) if ctl_type != CtlType::Text => {
+					// Set through the typed setter, so that the value can be checked when read back
+					let typed_value = CtlValue::parse(ctl_type, text).ok_or_else(|| Error::InvalidCtl {
+						key: key.to_owned(),
+						reason: format!("{:?} is not a valid {} value", text, ctl_type),
+					})?;
+
+					return self.ctl_set_value(key, &typed_value);
+				},
+				_ => {},
+			}
+		}
+
+		self.check_ctl(key, Some(value.ctl_type()))?;
+
+		if let CtlValue::FloatingPoint(factor) = *value {
+			if key == PLAY_TEMPO_FACTOR || key == PLAY_PITCH_FACTOR {
+				check_factor(key, factor)?;
+			}
+		}
+
+		let inner = self.inner;
+		let return_code = match *value {
+			CtlValue::Boolean(param) => with_string!(key, {
+				ffi::openmpt_module_ctl_set_boolean(inner, key, param as c_int)
+			}),
+			CtlValue::Integer(param) => with_string!(key, {
+				ffi::openmpt_module_ctl_set_integer(inner, key, param)
+			}),
+			CtlValue::FloatingPoint(param) => with_string!(key, {
+				ffi::openmpt_module_ctl_set_floatingpoint(inner, key, param)
+			}),
+			CtlValue::Text(ref param) => if self.ctl_set(key, param) { 1 } else { 0 },
+		};
+
+		if return_code != 1 {
+			return Err(Error::InvalidCtl {
+				key: key.to_owned(),
+				reason: format!("libopenmpt rejected value {}", value),
+			});
+		}
+
+		let read_back = self.ctl_get_value(key)?;
+		let applied = match (value, &read_back) {
+			(&CtlValue::FloatingPoint(expected), &CtlValue::FloatingPoint(actual)) => factors_match(actual, expected),
+			(expected, actual) => expected == actual,
+		};
+
+		if applied {
+			Ok(())
+		} else {
+			Err(Error::InvalidCtl {
+				key: key.to_owned(),
+				reason: format!("value {} was not applied, ctl is still {}", value, read_back),
+			})
+		}
+	}
+
+	// Check that the ctl exists, and that its type matches if known.
+	// Returns the type the ctl should be queried as.
+	fn check_ctl(&mut self, key: &str, expected_type: Option<CtlType>) -> Result<CtlType, Error> {
+		let ctls = self.get_ctls()?;
+		if !ctls.split(';').any(|ctl| ctl == key) {
+			return Err(Error::UnknownCtl(key.to_owned()));
+		}
+
+		let ctl_type = CtlType::of_known_ctl(key);
+
+		match (ctl_type, expected_type) {
+			(Some(actual), Some(expected)) if actual != expected => Err(Error::InvalidCtl {
+				key: key.to_owned(),
+				reason: format!("ctl holds a {} value, not a {} value", actual, expected),
+			}),
+			_ => Ok(expected_type.or(ctl_type).unwrap_or(CtlType::Text)),
+		}
 	}
 }
 
@@ -287,7 +823,7 @@ mod tests {
 	#[test]
 	fn all_known_ctls_are_supported() {
 		let mut module = test_helper::load_file_as_module("empty_module.xm").unwrap();
-		let keys = module.get_ctls();
+		let keys = module.get_ctls().unwrap();
 		
 		assert!(keys.contains(LOAD_SKIP_SAMPLES));
 		assert!(keys.contains(LOAD_SKIP_PATTERNS));
@@ -338,6 +874,132 @@ mod tests {
 		assert_eq!(module.ctl_get_dither().unwrap(), DitherMode::Simple);
 	}
 
+	#[test]
+	fn ctl_list_is_parsed() {
+		let ctls = parse_ctl_list("play.tempo_factor;dither;render.resampler.emulate_amiga;some.future_ctl;");
+
+		assert_eq!(ctls.len(), 4);
+		assert_eq!(ctls[0].ctl_type, Some(CtlType::FloatingPoint));
+		assert_eq!(ctls[1].ctl_type, Some(CtlType::Integer));
+		assert_eq!(ctls[2].ctl_type, Some(CtlType::Boolean));
+		assert_eq!(ctls[3], CtlInfo { key: "some.future_ctl".to_owned(), ctl_type: None });
+		assert_eq!(ctls[3].value_type(), CtlType::Text);
+	}
+
+	#[test]
+	fn ctl_values_are_formatted_for_ctl_set() {
+		assert_eq!(CtlValue::Boolean(true).to_string(), "1");
+		assert_eq!(CtlValue::Boolean(false).to_string(), "0");
+		assert_eq!(CtlValue::Integer(-3).to_string(), "-3");
+		assert_eq!(CtlValue::FloatingPoint(1.5).to_string(), "1.5");
+		assert_eq!(CtlValue::Text("fadeout".to_owned()).to_string(), "fadeout");
+	}
+
+	#[test]
+	fn typed_ctls_match_string_ctls() {
+		let mut module = test_helper::load_file_as_module("empty_module.xm").unwrap();
+
+		for info in module.ctl_infos().unwrap() {
+			let value = module.ctl_get_value(&info.key).unwrap();
+			assert_eq!(value.ctl_type(), info.value_type());
+			assert_eq!(module.ctl_get_text(&info.key).unwrap(), module.ctl_get(&info.key).unwrap());
+		}
+
+		assert_eq!(module.ctl_get_value(PLAY_TEMPO_FACTOR).unwrap(), CtlValue::FloatingPoint(1.0));
+		assert!(!module.ctl_get_boolean(LOAD_SKIP_SAMPLES).unwrap());
+
+		module.ctl_set_value(PLAY_PITCH_FACTOR, &CtlValue::FloatingPoint(2.0)).unwrap();
+		assert_eq!(module.ctl_get_play_pitch_factor().unwrap(), 2.0);
+	}
+
+	#[test]
+	fn typed_ctls_check_key_and_type() {
+		let mut module = test_helper::load_file_as_module("empty_module.xm").unwrap();
+
+		assert_eq!(module.ctl_get_value("invalid_ctl"), Err(Error::UnknownCtl("invalid_ctl".to_owned())));
+		assert!(matches!(module.ctl_get_boolean(PLAY_TEMPO_FACTOR), Err(Error::InvalidCtl { .. })));
+		assert!(matches!(module.ctl_set_value(DITHER, &CtlValue::Boolean(true)), Err(Error::InvalidCtl { .. })));
+	}
+
+	#[test]
+	fn text_values_are_parsed() {
+		assert_eq!(CtlValue::parse(CtlType::Boolean, "true"), Some(CtlValue::Boolean(true)));
+		assert_eq!(CtlValue::parse(CtlType::Boolean, "0"), Some(CtlValue::Boolean(false)));
+		assert_eq!(CtlValue::parse(CtlType::Boolean, "yes"), None);
+		assert_eq!(CtlValue::parse(CtlType::Integer, "2"), Some(CtlValue::Integer(2)));
+		assert_eq!(CtlValue::parse(CtlType::Integer, "2.5"), None);
+		assert_eq!(CtlValue::parse(CtlType::FloatingPoint, "2.5"), Some(CtlValue::FloatingPoint(2.5)));
+		assert_eq!(CtlValue::parse(CtlType::Text, "stop"), Some(CtlValue::Text("stop".to_owned())));
+	}
+
+	#[test]
+	fn text_values_are_checked_when_set() {
+		let mut module = test_helper::load_file_as_module("empty_module.xm").unwrap();
+		let dither = module.ctl_get_dither();
+
+		module.ctl_set_value(PLAY_TEMPO_FACTOR, &CtlValue::Text("1.5".to_owned())).unwrap();
+		assert_eq!(module.ctl_get_play_tempo_factor().unwrap(), 1.5);
+
+		assert!(matches!(module.ctl_set_value(DITHER, &CtlValue::Text("simple".to_owned())), Err(Error::InvalidCtl { .. })));
+		assert!(module.ctl_set_value(DITHER, &CtlValue::Text("26".to_owned())).is_err());
+		assert_eq!(module.ctl_get_dither(), dither);
+
+		module.ctl_set_value(PLAY_AT_END, &CtlValue::Text("stop".to_owned())).unwrap();
+		assert!(module.ctl_set_value(PLAY_AT_END, &CtlValue::Text("loop".to_owned())).is_err());
+	}
+
+	#[test]
+	fn amiga_filter_types_round_trip() {
+		for filter in &[AmigaFilterType::Auto, AmigaFilterType::A500, AmigaFilterType::A1200, AmigaFilterType::Unfiltered] {
+			assert_eq!(AmigaFilterType::from_str(filter.to_str()), Ok(*filter));
+		}
+
+		assert!(AmigaFilterType::from_str("a3000").is_err());
+	}
+
+	#[test]
+	fn at_end_behaviors_round_trip() {
+		for behavior in &[AtEndBehavior::Fadeout, AtEndBehavior::Continue, AtEndBehavior::Stop] {
+			assert_eq!(AtEndBehavior::from_str(behavior.to_str()), Ok(*behavior));
+		}
+
+		assert!(AtEndBehavior::from_str("loop").is_err());
+	}
+
+	#[test]
+	fn amiga_emulation_ctls_are_respected() {
+		let initial_ctls = vec!{
+			Ctl::EmulateAmigaResampler(true),
+			Ctl::AmigaResamplerFilter(AmigaFilterType::A1200),
+		};
+
+		let mut module = Module::create_from_memory(&test_helper::build_test_mod(), Logger::None, &initial_ctls).unwrap();
+
+		assert!(module.ctl_get_render_resampler_emulate_amiga().unwrap());
+		assert_eq!(module.ctl_get_render_resampler_emulate_amiga_type().unwrap(), AmigaFilterType::A1200);
+
+		module.ctl_set_render_resampler_emulate_amiga_type(AmigaFilterType::Unfiltered).unwrap();
+		assert_eq!(module.ctl_get_render_resampler_emulate_amiga_type().unwrap(), AmigaFilterType::Unfiltered);
+	}
+
+	#[test]
+	fn amiga_emulation_changes_mod_rendering() {
+		let render = |initial_ctls: &[Ctl]| {
+			let mut module = Module::create_from_memory(&test_helper::build_test_mod(), Logger::None, initial_ctls).unwrap();
+			let mut buffer = vec![0i16; 4800];
+			assert_eq!(module.read_mono(48000, &mut buffer), buffer.len());
+			buffer
+		};
+
+		let emulated = render(&[Ctl::EmulateAmigaResampler(true), Ctl::AmigaResamplerFilter(AmigaFilterType::A500)]);
+		let unfiltered = render(&[Ctl::EmulateAmigaResampler(true), Ctl::AmigaResamplerFilter(AmigaFilterType::Unfiltered)]);
+		let interpolated = render(&[Ctl::EmulateAmigaResampler(false)]);
+
+		assert!(emulated.iter().any(|&sample| sample != 0));
+		assert_ne!(emulated, interpolated);
+		assert_ne!(emulated, unfiltered);
+	}
+
 	#[test]
 	fn clean_result_for_getting_unknown_ctl() {
 		let mut module = test_helper::load_file_as_module("empty_module.xm").unwrap();
@@ -349,14 +1011,71 @@ mod tests {
 	fn clean_result_for_setting_invalid_ctl() {
 		let mut module = test_helper::load_file_as_module("empty_module.xm").unwrap();
 
-		try_set_ctl(&mut module, DITHER, "26");
+		let dither = module.ctl_get_dither();
+
+		assert!(module.ctl_set_value(DITHER, &CtlValue::Integer(26)).is_err());
+		assert_eq!(module.ctl_get_dither(), dither);
+	}
+
+	#[test]
+	fn invalid_initial_ctls_are_rejected() {
+		let data = test_helper::build_test_mod();
+
+		assert!(Module::create_from_memory(&data, Logger::None, &[Ctl::PlaybackTempoFactor(-1.0)]).is_err());
+		assert!(Module::create_from_memory(&data, Logger::None, &[Ctl::PlaybackTempoFactor(2.0)]).is_ok());
 	}
 
-	fn try_set_ctl (module: &mut Module, key: &str, new_val: &str) {
-		// Apparently, those only return false if the string pointers are invalid.
-		// assert!(!module.ctl_set(dither, "26"));
+	#[test]
+	fn out_of_range_factors_are_rejected() {
+		for &factor in &[0.0, -1.0, f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+			assert!(matches!(Ctl::PlaybackTempoFactor(factor).validate(), Err(Error::InvalidCtl { .. })));
+			assert!(matches!(Ctl::PlaybackPitchFactor(factor).validate(), Err(Error::InvalidCtl { .. })));
+		}
+
+		assert!(Ctl::PlaybackTempoFactor(0.5).validate().is_ok());
+		assert!(Ctl::DitherMode16Bit(DitherMode::None).validate().is_ok());
+	}
+
+	#[test]
+	fn read_back_values_are_compared() {
+		assert!(Ctl::SkipLoadingSamples(true).matches("1"));
+		assert!(!Ctl::SkipLoadingSamples(true).matches("0"));
+		assert!(!Ctl::SkipLoadingSamples(true).matches("yes"));
+		assert!(Ctl::PlaybackTempoFactor(1.1).matches("1.1"));
+		assert!(Ctl::PlaybackTempoFactor(1.1234567).matches("1.12346"));
+		assert!(!Ctl::PlaybackTempoFactor(1.5).matches("1"));
+		assert!(Ctl::DitherMode16Bit(DitherMode::Simple).matches("3"));
+		assert!(!Ctl::DitherMode16Bit(DitherMode::Simple).matches("26"));
+		assert!(Ctl::AmigaResamplerFilter(AmigaFilterType::A500).matches("a500"));
+		assert!(!Ctl::AtEnd(AtEndBehavior::Stop).matches("fadeout"));
+	}
+
+	#[test]
+	fn typed_setters_reject_invalid_values() {
+		let mut module = test_helper::load_file_as_module("empty_module.xm").unwrap();
+
+		let error = module.ctl_set_play_tempo_factor(-1.0).unwrap_err();
+		assert_eq!(error.to_string(), "invalid use of ctl \"play.tempo_factor\": factor must be a positive number, got -1");
+		assert!(module.ctl_set_play_pitch_factor(0.0).is_err());
+		assert!(module.ctl_set_value(PLAY_TEMPO_FACTOR, &CtlValue::FloatingPoint(-2.0)).is_err());
+
+		// Rejected values must not have been applied
+		assert_eq!(module.ctl_get_play_tempo_factor().unwrap(), 1.0);
+		assert_eq!(module.ctl_get_play_pitch_factor().unwrap(), 1.0);
+	}
+
+	#[test]
+	fn typed_setters_apply_valid_values() {
+		let mut module = test_helper::load_file_as_module("empty_module.xm").unwrap();
+
+		module.ctl_set_load_skip_samples(true).unwrap();
+		module.ctl_set_seek_sync_samples(true).unwrap();
+		module.ctl_set_play_tempo_factor(1.5).unwrap();
+		module.ctl_set_play_pitch_factor(0.75).unwrap();
+		module.ctl_set_dither(DitherMode::ModPlug).unwrap();
+		module.ctl_set_play_at_end(AtEndBehavior::Stop).unwrap();
 
-		module.ctl_set(key, "26");
-		println!("Tried setting {:?} at {:?}, now at {:?}", key, new_val, module.ctl_get(DITHER).unwrap());
+		assert_eq!(module.ctl_get_play_tempo_factor().unwrap(), 1.5);
+		assert_eq!(module.ctl_get_dither().unwrap(), DitherMode::ModPlug);
 	}
 }
\ No newline at end of file
@@ -3,6 +3,7 @@
 
 use super::Module;
 use openmpt_sys;
+use ffi;
 use std::os::raw::*;
 
 impl Module {
@@ -26,6 +27,16 @@ impl Module {
 		if return_code == 0 { false } else { true }
 	}
 
+	/// Get the currently selected sub-song.
+	///
+	/// ### Returns
+	/// Index of the sub-song, or -1 if all sub-songs are played consecutively.
+	pub fn get_selected_subsong(&mut self) -> i32 {
+		unsafe {
+			ffi::openmpt_module_get_selected_subsong(self.inner)
+		}
+	}
+
 	/// Set approximate current song position.
 	///
 	/// ### Parameters
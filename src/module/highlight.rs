@@ -0,0 +1,320 @@
+//! Definitions for rendering pattern data as highlighted text,
+//! combining `Cell::get_formatted` with `Cell::get_highlight`.
+//!
+//! Each character of a highlight string gives the class of the character at the
+//! same position in the formatted string, which is turned into ANSI escape codes
+//! for terminals or `<span>` elements for HTML.
+
+use super::Module;
+use super::iteration::{Pattern, Row};
+use error::Error;
+
+const ANSI_RESET:&str = "\x1b[0m";
+
+/// The class of a formatted pattern character, as given by its highlight character.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HighlightClass {
+	/// Blank space between columns (` `)
+	Space,
+	/// Placeholder for empty columns (`.`)
+	Empty,
+	/// A note (`n`)
+	Note,
+	/// A special note, such as note-off or note-cut (`m`)
+	SpecialNote,
+	/// An instrument number (`i`)
+	Instrument,
+	/// A volume column effect (`u`)
+	VolumeEffect,
+	/// A volume column parameter (`v`)
+	VolumeParam,
+	/// An effect column effect (`e`)
+	Effect,
+	/// An effect column parameter (`f`)
+	EffectParam,
+	/// Any highlight character unknown to this crate
+	Other(char),
+}
+
+impl HighlightClass {
+	/// Get the class of a highlight character.
+	pub fn from_char(highlight: char) -> HighlightClass {
+		match highlight {
+			' ' => HighlightClass::Space,
+			'.' => HighlightClass::Empty,
+			'n' => HighlightClass::Note,
+			'm' => HighlightClass::SpecialNote,
+			'i' => HighlightClass::Instrument,
+			'u' => HighlightClass::VolumeEffect,
+			'v' => HighlightClass::VolumeParam,
+			'e' => HighlightClass::Effect,
+			'f' => HighlightClass::EffectParam,
+			other => HighlightClass::Other(other),
+		}
+	}
+
+	/// Get the ANSI SGR parameters used for this class, None for unstyled text.
+	pub fn ansi_code(self) -> Option<&'static str> {
+		match self {
+			HighlightClass::Empty => Some("90"),
+			HighlightClass::Note => Some("97"),
+			HighlightClass::SpecialNote => Some("95"),
+			HighlightClass::Instrument => Some("36"),
+			HighlightClass::VolumeEffect => Some("32"),
+			HighlightClass::VolumeParam => Some("92"),
+			HighlightClass::Effect => Some("31"),
+			HighlightClass::EffectParam => Some("33"),
+			HighlightClass::Space | HighlightClass::Other(_) => None,
+		}
+	}
+
+	/// Get the CSS class used for this class in HTML output, None for unstyled text.
+	pub fn css_class(self) -> Option<&'static str> {
+		match self {
+			HighlightClass::Empty => Some("mpt-empty"),
+			HighlightClass::Note => Some("mpt-note"),
+			HighlightClass::SpecialNote => Some("mpt-special-note"),
+			HighlightClass::Instrument => Some("mpt-instrument"),
+			HighlightClass::VolumeEffect => Some("mpt-volume-effect"),
+			HighlightClass::VolumeParam => Some("mpt-volume-param"),
+			HighlightClass::Effect => Some("mpt-effect"),
+			HighlightClass::EffectParam => Some("mpt-effect-param"),
+			HighlightClass::Space | HighlightClass::Other(_) => None,
+		}
+	}
+}
+
+/// The kind of markup to produce.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HighlightStyle {
+	/// Formatted text only, without highlighting
+	Plain,
+	/// ANSI escape codes, for terminals
+	Ansi,
+	/// HTML `<span>` elements using the classes from `HighlightClass::css_class`,
+	/// with the text escaped. Meant to go in a `<pre>` element.
+	Html,
+}
+
+/// Settings for `Module::render_pattern`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatternTextOptions {
+	pub style: HighlightStyle,
+	/// Width of each cell in characters, 0 for their natural width
+	pub cell_width: usize,
+	/// Channels to include, in order. None for every channel
+	pub channels: Option<Vec<i32>>,
+	/// Start each line with the row number
+	pub row_numbers: bool,
+	/// Text placed between cells
+	pub separator: String,
+}
+
+impl Default for PatternTextOptions {
+	fn default() -> PatternTextOptions {
+		PatternTextOptions {
+			style: HighlightStyle::Ansi,
+			cell_width: 13,
+			channels: None,
+			row_numbers: true,
+			separator: "|".to_owned(),
+		}
+	}
+}
+
+/// Combine formatted cell content with its highlighting string.
+///
+/// ### Parameters
+/// * `formatted` : Cell content, as returned by `Cell::get_formatted`.
+/// * `highlight` : Highlighting string for that content, as returned by `Cell::get_highlight`.
+/// * `style` : The kind of markup to produce.
+///
+/// ### Returns
+/// The content with markup added, merging consecutive characters of the same class.
+pub fn highlight_text(formatted: &str, highlight: &str, style: HighlightStyle) -> String {
+	let mut output = String::with_capacity(formatted.len() * 2);
+	let mut highlights = highlight.chars();
+	let mut current: Option<&'static str> = None;
+
+	for character in formatted.chars() {
+		let class = HighlightClass::from_char(highlights.next().unwrap_or(' '));
+		let markup = match style {
+			HighlightStyle::Plain => None,
+			HighlightStyle::Ansi => class.ansi_code(),
+			HighlightStyle::Html => class.css_class(),
+		};
+
+		if markup != current {
+			close_markup(&mut output, current, style);
+			match (markup, style) {
+				(Some(code), HighlightStyle::Ansi) => output.push_str(&format!("\x1b[{}m", code)),
+				(Some(css_class), HighlightStyle::Html) => output.push_str(&format!("<span class=\"{}\">", css_class)),
+				_ => {},
+			}
+			current = markup;
+		}
+
+		match (character, style) {
+			('&', HighlightStyle::Html) => output.push_str("&amp;"),
+			('<', HighlightStyle::Html) => output.push_str("&lt;"),
+			('>', HighlightStyle::Html) => output.push_str("&gt;"),
+			(character, _) => output.push(character),
+		}
+	}
+
+	close_markup(&mut output, current, style);
+	output
+}
+
+fn close_markup(output: &mut String, current: Option<&'static str>, style: HighlightStyle) {
+	match (current, style) {
+		(Some(_), HighlightStyle::Ansi) => output.push_str(ANSI_RESET),
+		(Some(_), HighlightStyle::Html) => output.push_str("</span>"),
+		_ => {},
+	}
+}
+
+impl Module {
+	/// Render a whole pattern as highlighted text.
+	///
+	/// ### Parameters
+	/// * `pattern_num` : The index of the pattern that should be rendered.
+	/// * `options` : How to lay out and highlight the pattern.
+	///
+	/// ### Returns
+	/// One line per row, each ending with a newline, or an error if
+	/// the pattern or one of the selected channels doesn't exist.
+	pub fn render_pattern(&mut self, pattern_num: i32, options: &PatternTextOptions) -> Result<String, Error> {
+		let channels = self.selected_channels(options)?;
+		let mut pattern = self.pattern_or_error(pattern_num)?;
+		let num_rows = pattern.get_num_rows();
+
+		let mut output = String::new();
+
+		for row_num in 0..num_rows {
+			let mut row = pattern.get_row_by_number(row_num)?;
+			output.push_str(&render_row(&mut row, row_num, &channels, options)?);
+			output.push('\n');
+		}
+
+		Ok(output)
+	}
+
+	/// Render a single pattern row as highlighted text.
+	///
+	/// ### Parameters
+	/// * `pattern_num` : The index of the pattern the row belongs to.
+	/// * `row_num` : The index of the row in that pattern.
+	/// * `options` : How to lay out and highlight the row.
+	///
+	/// ### Returns
+	/// The row as a single line, without a trailing newline, or an error if
+	/// the pattern, the row or one of the selected channels doesn't exist.
+	pub fn render_pattern_row(&mut self, pattern_num: i32, row_num: i32, options: &PatternTextOptions) -> Result<String, Error> {
+		let channels = self.selected_channels(options)?;
+		let mut pattern = self.pattern_or_error(pattern_num)?;
+		let mut row = pattern.get_row_by_number(row_num)?;
+
+		render_row(&mut row, row_num, &channels, options)
+	}
+
+	fn selected_channels(&mut self, options: &PatternTextOptions) -> Result<Vec<i32>, Error> {
+		let num_channels = self.get_num_channels();
+		let channels = match options.channels {
+			Some(ref channels) => channels.clone(),
+			None => (0..num_channels).collect(),
+		};
+
+		for &channel_num in &channels {
+			Error::check_index("channel", channel_num, num_channels)?;
+		}
+
+		Ok(channels)
+	}
+
+	fn pattern_or_error(&mut self, pattern_num: i32) -> Result<Pattern<'_>, Error> {
+		let num_patterns = self.get_num_patterns();
+		self.get_pattern_by_number(pattern_num)
+			.ok_or(Error::IndexOutOfRange { item: "pattern", index: pattern_num, count: num_patterns })
+	}
+}
+
+fn render_row(row: &mut Row, row_num: i32, channels: &[i32], options: &PatternTextOptions) -> Result<String, Error> {
+	let pad = options.cell_width > 0;
+	let mut output = String::new();
+
+	if options.row_numbers {
+		output.push_str(&format!("{:>3}", row_num));
+	}
+
+	for (index, &channel_num) in channels.iter().enumerate() {
+		if options.row_numbers || index > 0 {
+			output.push_str(&highlight_text(&options.separator, "", options.style));
+		}
+
+		let mut cell = row.get_cell_by_channel(channel_num)?;
+		let formatted = cell.get_formatted(options.cell_width, pad)?;
+		let highlight = cell.get_highlight(options.cell_width, pad)?;
+		output.push_str(&highlight_text(&formatted, &highlight, options.style));
+	}
+
+	Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::test_helper;
+
+	#[test]
+	fn highlight_characters_are_classified() {
+		assert_eq!(HighlightClass::from_char('n'), HighlightClass::Note);
+		assert_eq!(HighlightClass::from_char('f'), HighlightClass::EffectParam);
+		assert_eq!(HighlightClass::from_char('?'), HighlightClass::Other('?'));
+		assert_eq!(HighlightClass::Other('?').ansi_code(), None);
+	}
+
+	#[test]
+	fn ansi_spans_are_merged() {
+		let text = highlight_text("C-5 01 ...", "nnn ii ...", HighlightStyle::Ansi);
+		assert_eq!(text, "\x1b[97mC-5\x1b[0m \x1b[36m01\x1b[0m \x1b[90m...\x1b[0m");
+	}
+
+	#[test]
+	fn html_is_escaped() {
+		let text = highlight_text("=== <>", "mmm ..", HighlightStyle::Html);
+		assert_eq!(text, "<span class=\"mpt-special-note\">===</span> <span class=\"mpt-empty\">&lt;&gt;</span>");
+	}
+
+	#[test]
+	fn plain_style_and_short_highlights() {
+		assert_eq!(highlight_text("C-5 01", "nnn ii", HighlightStyle::Plain), "C-5 01");
+		assert_eq!(highlight_text("C-5 01", "nnn", HighlightStyle::Ansi), "\x1b[97mC-5\x1b[0m 01");
+	}
+
+	#[test]
+	fn rendered_pattern_matches_formatted_cells() {
+		let mut module = test_helper::load_file_as_module("UNATCO.it").unwrap();
+		let options = PatternTextOptions { style: HighlightStyle::Plain, channels: Some(vec![1, 0]), ..PatternTextOptions::default() };
+		let text = module.render_pattern(0, &options).unwrap();
+
+		let mut pattern = module.get_pattern_by_number(0).unwrap();
+		let num_rows = pattern.get_num_rows();
+		let mut row = pattern.get_row_by_number(0).unwrap();
+		let second = row.get_cell_by_channel(0).unwrap().get_formatted(13, true).unwrap();
+		let first = row.get_cell_by_channel(1).unwrap().get_formatted(13, true).unwrap();
+
+		assert_eq!(text.lines().count(), num_rows as usize);
+		assert_eq!(text.lines().next().unwrap(), format!("  0|{}|{}", first, second));
+		assert_eq!(module.render_pattern_row(0, 0, &options).unwrap(), text.lines().next().unwrap());
+	}
+
+	#[test]
+	fn invalid_channels_are_rejected() {
+		let mut module = test_helper::load_file_as_module("empty_module.xm").unwrap();
+		let options = PatternTextOptions { channels: Some(vec![1000]), ..PatternTextOptions::default() };
+
+		assert!(module.render_pattern(0, &options).is_err());
+		assert!(module.render_pattern(1000, &PatternTextOptions::default()).is_err());
+	}
+}
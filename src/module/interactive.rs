@@ -0,0 +1,208 @@
+//! Definitions for all methods used to interact with playback
+//! in real time, through the libopenmpt_ext interactive interfaces.
+//!
+//! ## Remarks
+//! Those interfaces were added in later versions of libopenmpt
+//! (`interactive2` in 0.6.0, `interactive3` in 0.7.0). Every method here
+//! checks the version of the linked library before querying the interface,
+//! and returns `Error::Unsupported` if it is missing.
+
+use super::Module;
+use error::Error;
+use ffi;
+use info;
+use std::mem;
+use std::os::raw::*;
+
+impl Module {
+	/// Trigger a note-off on a channel.
+	///
+	/// ### Parameters
+	/// * `channel_num` : The channel on which the note should be released.
+	///
+	/// ### Returns
+	/// An error if the interface is unavailable or the channel is out of range.
+	///
+	/// ### Remarks
+	/// Unlike `note_fade`, this lets the instrument envelopes run their course.
+	pub fn note_off(&mut self, channel_num: i32) -> Result<(), Error> {
+		let interface = self.get_interactive2()?;
+		let note_off = interface.note_off.ok_or(Error::Unsupported(ffi::INTERFACE_INTERACTIVE2))?;
+
+		let return_code = unsafe {
+			note_off(self.ext, channel_num)
+		};
+
+		if return_code == 1 { Ok(()) } else { Err(Error::OperationFailed("release note")) }
+	}
+
+	/// Trigger a note fade-out on a channel.
+	///
+	/// ### Parameters
+	/// * `channel_num` : The channel on which the note should be faded.
+	///
+	/// ### Returns
+	/// An error if the interface is unavailable or the channel is out of range.
+	pub fn note_fade(&mut self, channel_num: i32) -> Result<(), Error> {
+		let interface = self.get_interactive2()?;
+		let note_fade = interface.note_fade.ok_or(Error::Unsupported(ffi::INTERFACE_INTERACTIVE2))?;
+
+		let return_code = unsafe {
+			note_fade(self.ext, channel_num)
+		};
+
+		if return_code == 1 { Ok(()) } else { Err(Error::OperationFailed("fade note")) }
+	}
+
+	/// Set the panning position of a channel.
+	///
+	/// ### Parameters
+	/// * `channel_num` : The pattern channel whose panning should be changed.
+	/// * `panning` : The new panning position, in [-1.0,1.0] (full left to full right).
+	///
+	/// ### Returns
+	/// An error if the interface is unavailable or one of the parameters is out of range.
+	pub fn set_channel_panning(&mut self, channel_num: i32, panning: c_double) -> Result<(), Error> {
+		self.check_pattern_channel(channel_num)?;
+		let interface = self.get_interactive2()?;
+		let set_channel_panning = interface.set_channel_panning.ok_or(Error::Unsupported(ffi::INTERFACE_INTERACTIVE2))?;
+
+		let return_code = unsafe {
+			set_channel_panning(self.ext, channel_num, panning)
+		};
+
+		if return_code == 1 { Ok(()) } else { Err(Error::OperationFailed("set channel panning")) }
+	}
+
+	/// Get the panning position of a channel.
+	///
+	/// ### Parameters
+	/// * `channel_num` : The pattern channel whose panning should be retrieved.
+	///
+	/// ### Returns
+	/// The current panning position, in [-1.0,1.0] (full left to full right).
+	pub fn get_channel_panning(&mut self, channel_num: i32) -> Result<c_double, Error> {
+		self.check_pattern_channel(channel_num)?;
+		let interface = self.get_interactive2()?;
+		let get_channel_panning = interface.get_channel_panning.ok_or(Error::Unsupported(ffi::INTERFACE_INTERACTIVE2))?;
+
+		Ok(unsafe {
+			get_channel_panning(self.ext, channel_num)
+		})
+	}
+
+	/// Set the finetune of the note currently playing on a channel.
+	///
+	/// ### Parameters
+	/// * `channel_num` : The channel whose note should be detuned.
+	/// * `finetune` : The finetune, in semitones (e.g. 0.5 is a quarter-tone up).
+	///
+	/// ### Returns
+	/// An error if the interface is unavailable or the channel is out of range.
+	///
+	/// ### Remarks
+	/// The finetune is reset whenever a new note is triggered on that channel.
+	pub fn set_note_finetune(&mut self, channel_num: i32, finetune: c_double) -> Result<(), Error> {
+		let interface = self.get_interactive2()?;
+		let set_note_finetune = interface.set_note_finetune.ok_or(Error::Unsupported(ffi::INTERFACE_INTERACTIVE2))?;
+
+		let return_code = unsafe {
+			set_note_finetune(self.ext, channel_num, finetune)
+		};
+
+		if return_code == 1 { Ok(()) } else { Err(Error::OperationFailed("set note finetune")) }
+	}
+
+	/// Get the finetune of the note currently playing on a channel.
+	///
+	/// ### Parameters
+	/// * `channel_num` : The channel whose note finetune should be retrieved.
+	///
+	/// ### Returns
+	/// The finetune, in semitones.
+	pub fn get_note_finetune(&mut self, channel_num: i32) -> Result<c_double, Error> {
+		let interface = self.get_interactive2()?;
+		let get_note_finetune = interface.get_note_finetune.ok_or(Error::Unsupported(ffi::INTERFACE_INTERACTIVE2))?;
+
+		Ok(unsafe {
+			get_note_finetune(self.ext, channel_num)
+		})
+	}
+
+	/// Set the current tempo directly, overriding the one set by the module.
+	///
+	/// ### Parameters
+	/// * `tempo` : The new tempo in tracker units, which may be fractional. Its exact meaning depends on the tempo mode being used.
+	///
+	/// ### Returns
+	/// An error if the interface is unavailable or the tempo is out of range (usually [32,512]).
+	///
+	/// ### Remarks
+	/// The tempo may be reset by pattern commands at any time.
+	pub fn set_current_tempo2(&mut self, tempo: c_double) -> Result<(), Error> {
+		let interface = self.get_interactive3()?;
+		let set_current_tempo2 = interface.set_current_tempo2.ok_or(Error::Unsupported(ffi::INTERFACE_INTERACTIVE3))?;
+
+		let return_code = unsafe {
+			set_current_tempo2(self.ext, tempo)
+		};
+
+		if return_code == 1 { Ok(()) } else { Err(Error::OperationFailed("set current tempo")) }
+	}
+
+	fn check_pattern_channel(&mut self, channel_num: i32) -> Result<(), Error> {
+		let num_channels = self.get_num_channels();
+		Error::check_index("channel", channel_num, num_channels)
+	}
+
+	fn get_interactive2(&mut self) -> Result<ffi::openmpt_module_ext_interface_interactive2, Error> {
+		let mut interface = ffi::openmpt_module_ext_interface_interactive2::default();
+		self.get_interface(ffi::INTERFACE_INTERACTIVE2, info::LibraryVersion(0, 6, 0), &mut interface)?;
+		Ok(interface)
+	}
+
+	fn get_interactive3(&mut self) -> Result<ffi::openmpt_module_ext_interface_interactive3, Error> {
+		let mut interface = ffi::openmpt_module_ext_interface_interactive3::default();
+		self.get_interface(ffi::INTERFACE_INTERACTIVE3, info::LibraryVersion(0, 7, 0), &mut interface)?;
+		Ok(interface)
+	}
+
+	fn get_interface<T>(&mut self, interface_id: &'static str, min_version: info::LibraryVersion, interface: &mut T) -> Result<(), Error> {
+		info::require_version(min_version, interface_id)?;
+
+		let name = interface_id;
+		let interface_ptr: *mut T = interface;
+		let return_code = with_string!(name, {
+			ffi::openmpt_module_ext_get_interface(self.ext, name, interface_ptr as *mut c_void, mem::size_of::<T>())
+		});
+
+		if return_code == 1 { Ok(()) } else { Err(Error::Unsupported(interface_id)) }
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::super::test_helper;
+	use error::Error;
+
+	#[test]
+	fn channel_panning_is_applied_when_supported() {
+		let mut module = test_helper::load_file_as_module("UNATCO.it").unwrap();
+
+		match module.set_channel_panning(0, -0.5) {
+			Err(Error::Unsupported(_)) => return, // Linked against an older libopenmpt
+			result => assert!(result.is_ok()),
+		}
+
+		assert_eq!(module.get_channel_panning(0).unwrap(), -0.5);
+	}
+
+	#[test]
+	fn out_of_range_channel_panning_is_rejected() {
+		let mut module = test_helper::load_file_as_module("UNATCO.it").unwrap();
+		let num_channels = module.get_num_channels();
+
+		assert!(module.set_channel_panning(num_channels, 0.0).is_err());
+		assert!(module.get_channel_panning(-1).is_err());
+	}
+}
@@ -0,0 +1,173 @@
+//! Definitions for the listing of everything a module contains,
+//! meant for cataloging purposes.
+
+use openmpt_sys;
+use super::Module;
+use error::Error;
+use std::os::raw::*;
+
+/// A listing of the contents of a module, obtained with `Module::inventory`.
+///
+/// With the `serde` feature enabled, this can be serialized for storage.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize))]
+pub struct Inventory {
+	/// The name of every instrument slot, by index.
+	pub instruments: Vec<String>,
+	/// The name of every sample slot, by index.
+	pub samples: Vec<String>,
+	/// The name of every pattern channel, by index.
+	pub channels: Vec<String>,
+	/// Every sub-song, by index.
+	pub subsongs: Vec<SubsongInfo>,
+	/// The number of rows of every pattern, by pattern number.
+	pub pattern_rows: Vec<i32>,
+	/// The pattern number found at each order position of the current sequence.
+	pub orders: Vec<i32>,
+}
+
+/// The name and length of a sub-song, as listed in an `Inventory`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize))]
+pub struct SubsongInfo {
+	/// The sub-song name.
+	pub name: String,
+	/// The approximate duration of the sub-song in seconds.
+	pub duration_seconds: c_double,
+}
+
+impl Inventory {
+	/// Get the number of distinct patterns listed.
+	pub fn num_patterns(&self) -> i32 {
+		self.pattern_rows.len() as i32
+	}
+}
+
+impl Module {
+	/// List the contents of the module.
+	///
+	/// ### Returns
+	/// The names of all instruments, samples, channels and sub-songs,
+	/// along with the sub-song durations and the pattern and order layout,
+	/// or an error if libopenmpt fails to return one of the names.
+	///
+	/// ### Remarks
+	/// Measuring sub-song durations requires selecting each sub-song in turn.
+	/// The selected sub-song and the playback position are restored afterwards,
+	/// the latter only approximately (see `set_position_seconds`).
+	pub fn inventory(&mut self) -> Result<Inventory, Error> {
+		let num_instruments = self.get_num_instruments();
+		let instruments = (0..num_instruments).map(|num| self.get_instrument_name(num)).collect::<Result<_, _>>()?;
+
+		let num_samples = self.get_num_samples();
+		let samples = (0..num_samples).map(|num| self.get_sample_name(num)).collect::<Result<_, _>>()?;
+
+		let num_channels = self.get_num_channels();
+		let channels = (0..num_channels).map(|num| self.get_channel_name(num)).collect::<Result<_, _>>()?;
+
+		let num_patterns = self.get_num_patterns();
+		let pattern_rows = (0..num_patterns).map(|pattern_num| unsafe {
+			openmpt_sys::openmpt_module_get_pattern_num_rows(self.inner, pattern_num)
+		}).collect();
+
+		// Read before selecting other sub-songs, which may use other sequences
+		let orders = self.orders().collect();
+
+		let subsongs = self.subsong_infos()?;
+
+		Ok(Inventory {
+			instruments,
+			samples,
+			channels,
+			subsongs,
+			pattern_rows,
+			orders,
+		})
+	}
+
+	fn subsong_infos(&mut self) -> Result<Vec<SubsongInfo>, Error> {
+		let num_subsongs = self.get_num_subsongs();
+		let names = (0..num_subsongs).map(|num| self.get_subsong_name(num)).collect::<Result<Vec<_>, _>>()?;
+
+		let saved_subsong = self.get_selected_subsong();
+		let saved_position = self.get_position_seconds();
+		let mut subsongs = Vec::with_capacity(names.len());
+
+		for (subsong_num, name) in names.into_iter().enumerate() {
+			if !self.select_subsong(subsong_num as i32) {
+				break;
+			}
+
+			subsongs.push(SubsongInfo {
+				name,
+				duration_seconds: self.get_duration_seconds(),
+			});
+		}
+
+		self.select_subsong(saved_subsong);
+		self.set_position_seconds(saved_position);
+
+		if subsongs.len() == num_subsongs as usize {
+			Ok(subsongs)
+		} else {
+			Err(Error::OperationFailed("select sub-song"))
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::super::test_helper;
+
+	#[test]
+	fn dummy_file_inventory_matches_getters() {
+		let mut module = test_helper::load_file_as_module("empty_module.xm").unwrap();
+		let inventory = module.inventory().unwrap();
+
+		assert_eq!(inventory.instruments.len() as i32, module.get_num_instruments());
+		assert_eq!(inventory.samples.len() as i32, module.get_num_samples());
+		assert_eq!(inventory.channels.len() as i32, module.get_num_channels());
+		assert_eq!(inventory.subsongs.len() as i32, module.get_num_subsongs());
+		assert_eq!(inventory.num_patterns(), module.get_num_patterns());
+		assert_eq!(inventory.orders.len() as i32, module.get_num_orders());
+
+		for (channel_num, name) in inventory.channels.iter().enumerate() {
+			assert_eq!(name, &module.get_channel_name(channel_num as i32).unwrap());
+		}
+	}
+
+	#[test]
+	fn unatco_inventory_lists_subsongs() {
+		let mut module = test_helper::load_file_as_module("UNATCO.it").unwrap();
+		let inventory = module.inventory().unwrap();
+
+		// Main, Game over, Dialogue /w intro, Combat, Dialogue loop
+		assert_eq!(inventory.subsongs.len(), 5);
+
+		for subsong in &inventory.subsongs {
+			assert!(subsong.duration_seconds > 0.0);
+		}
+
+		for (pattern_num, &num_rows) in inventory.pattern_rows.iter().enumerate() {
+			let mut pattern = module.get_pattern_by_number(pattern_num as i32).unwrap();
+			assert_eq!(num_rows, pattern.get_num_rows());
+		}
+	}
+
+	#[test]
+	fn inventory_keeps_selected_subsong() {
+		let mut module = test_helper::load_file_as_module("UNATCO.it").unwrap();
+		assert!(module.select_subsong(1));
+		let subsong_duration = module.get_duration_seconds();
+		module.set_position_seconds(subsong_duration / 2.0);
+		let (order, row) = (module.get_current_order(), module.get_current_row());
+
+		let inventory = module.inventory().unwrap();
+
+		assert_eq!(module.get_selected_subsong(), 1);
+		assert_eq!(module.get_duration_seconds(), subsong_duration);
+		assert_eq!(inventory.subsongs[1].duration_seconds, subsong_duration);
+		assert_eq!((module.get_current_order(), module.get_current_row()), (order, row));
+		assert_eq!(inventory.orders, module.orders().collect::<Vec<_>>());
+	}
+}
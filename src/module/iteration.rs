@@ -4,7 +4,16 @@
 use openmpt_sys;
 use super::Module;
 use super::super::mod_command::ModCommand;
+use error::Error;
+#[cfg(feature = "libopenmpt-0-8")]
+use ffi;
+#[cfg(feature = "libopenmpt-0-8")]
+use info;
 use std::os::raw::c_int;
+use std::vec;
+
+#[cfg(feature = "libopenmpt-0-8")]
+const PATTERN_TIME_SIGNATURE_VERSION:info::LibraryVersion = info::LibraryVersion(0, 8, 0);
 
 pub struct Pattern<'m> {
 	module: &'m mut Module,
@@ -21,6 +30,31 @@ pub struct Cell<'r, 'p:'r, 'm:'p> {
 	channel_num: i32,
 }
 
+/// Iterator over the rows of a pattern, returned by `Pattern::rows`.
+///
+/// Each row is read in full as it is reached, and yielded as a list of
+/// ModCommand (one per channel), or an error message if one of its cells is invalid.
+///
+/// Every row from 0 to the number of rows is yielded : the module cannot change
+/// while it is borrowed, so the rows are always in range.
+pub struct Rows<'p, 'm:'p> {
+	pattern: &'p mut Pattern<'m>,
+	next_row: i32,
+	num_rows: i32,
+}
+
+/// Iterator over the cells of a row, returned by `Row::cells`.
+///
+/// Each cell is yielded as a ModCommand, or an error message if it is invalid.
+///
+/// Every channel of the module is yielded : the module cannot change
+/// while it is borrowed, so the channels are always in range.
+pub struct Cells<'r, 'p:'r, 'm:'p> {
+	row: &'r mut Row<'p, 'm>,
+	next_channel: i32,
+	num_channels: i32,
+}
+
 impl Module {
 	/// Get pattern at order position.
 	///
@@ -57,6 +91,24 @@ impl Module {
 		}
 	}
 
+	/// Iterate over the order list.
+	///
+	/// ### Returns
+	/// An iterator over the pattern number found at each order position of the current sequence.
+	///
+	/// ### Remarks
+	/// The order list is read all at once, so the module can still be used while iterating.
+	/// Separator and skip orders yield pattern numbers for which `get_pattern_by_number` returns None.
+	pub fn orders(&mut self) -> vec::IntoIter<i32> {
+		let num_orders = self.get_num_orders();
+
+		let orders: Vec<i32> = (0..num_orders).map(|order_num| unsafe {
+			openmpt_sys::openmpt_module_get_order_pattern(self.inner, order_num)
+		}).collect();
+
+		orders.into_iter()
+	}
+
 	/// Get the number of distinct patterns for that module.
 	///
 	/// ### Returns
@@ -133,13 +185,16 @@ impl Module {
 	/// * `instrument_num` : The index of the instrument whose name should be retrieved
 	///
 	/// ### Returns
-	/// The instrument name.
-	pub fn get_instrument_name (&mut self, instrument_num: i32) -> String {
+	/// The instrument name, or an error if the index is out of range.
+	pub fn get_instrument_name (&mut self, instrument_num: i32) -> Result<String, Error> {
+		let count = self.get_num_instruments();
+		Error::check_index("instrument", instrument_num, count)?;
+
 		let opt_string = get_string!{
 			openmpt_sys::openmpt_module_get_instrument_name(self.inner, instrument_num)
 		};
-		
-		opt_string.expect("Got null pointer instead of string")
+
+		opt_string.ok_or(Error::NullString("instrument name"))
 	}
 	
 	/// Get a sample name.
@@ -148,13 +203,16 @@ impl Module {
 	/// * `sample_num` : The index of the sample whose name should be retrieved
 	///
 	/// ### Returns
-	/// The sample name.
-	pub fn get_sample_name (&mut self, sample_num: i32) -> String {
+	/// The sample name, or an error if the index is out of range.
+	pub fn get_sample_name (&mut self, sample_num: i32) -> Result<String, Error> {
+		let count = self.get_num_samples();
+		Error::check_index("sample", sample_num, count)?;
+
 		let opt_string = get_string!{
 			openmpt_sys::openmpt_module_get_sample_name(self.inner, sample_num)
 		};
 
-		opt_string.expect("Got null pointer instead of string")
+		opt_string.ok_or(Error::NullString("sample name"))
 	}
 
 	/// Get a channel name.
@@ -163,13 +221,16 @@ impl Module {
 	/// * `channel_num` : The index of the channel whose name should be retrieved
 	///
 	/// ### Returns
-	/// The channel name.
-	pub fn get_channel_name (&mut self, channel_num: i32) -> String {
+	/// The channel name, or an error if the index is out of range.
+	pub fn get_channel_name (&mut self, channel_num: i32) -> Result<String, Error> {
+		let count = self.get_num_channels();
+		Error::check_index("channel", channel_num, count)?;
+
 		let opt_string = get_string!{
 			openmpt_sys::openmpt_module_get_channel_name(self.inner, channel_num)
 		};
 
-		opt_string.expect("Got null pointer instead of string")
+		opt_string.ok_or(Error::NullString("channel name"))
 	}
 
 	/// Get a sub-song name.
@@ -178,13 +239,16 @@ impl Module {
 	/// * `subsong_num` : The index of the sub-song whose name should be retrieved
 	///
 	/// ### Returns
-	/// The sub-song name.
-	pub fn get_subsong_name (&mut self, subsong_num: i32) -> String {
+	/// The sub-song name, or an error if the index is out of range.
+	pub fn get_subsong_name (&mut self, subsong_num: i32) -> Result<String, Error> {
+		let count = self.get_num_subsongs();
+		Error::check_index("subsong", subsong_num, count)?;
+
 		let opt_string = get_string!{
 			openmpt_sys::openmpt_module_get_subsong_name(self.inner, subsong_num)
 		};
 
-		opt_string.expect("Got null pointer instead of string")
+		opt_string.ok_or(Error::NullString("subsong name"))
 	}
 }
 
@@ -195,30 +259,35 @@ impl<'m> Pattern<'m> {
 	/// * `row_num` : The index of the row that should be retrieved.
 	///
 	/// ### Returns
-	/// A Row wrapper for the row, or None if no such row exists.
-	pub fn get_row_by_number<'p> (&'p mut self, row_num: i32) -> Option<Row<'p, 'm>> {
+	/// A Row wrapper for the row, or an error if no such row exists.
+	pub fn get_row_by_number<'p> (&'p mut self, row_num: i32) -> Result<Row<'p, 'm>, Error> {
+		// Also covers patterns that do not exist, which have no rows
 		let pattern_num_rows = self.get_num_rows();
+		Error::check_index("row", row_num, pattern_num_rows)?;
 
-		assert_ne!(pattern_num_rows, 0); // Pattern does not exist
-		
-		if row_num < 0 || row_num >= pattern_num_rows {
-			None
-		} else {
-			Some(Row{ num : row_num, pattern: self })
-		}
+		Ok(Row{ num : row_num, pattern: self })
+	}
+
+	/// Iterate over the rows of this pattern.
+	///
+	/// ### Returns
+	/// An iterator yielding the content of each row, in order.
+	pub fn rows<'p> (&'p mut self) -> Rows<'p, 'm> {
+		let num_rows = self.get_num_rows();
+		Rows { pattern: self, next_row: 0, num_rows }
 	}
 
 	/// Get name for this pattern/order.
 	///
 	/// ### Returns
 	/// The pattern name.
-	pub fn get_name (&mut self) -> String {
+	pub fn get_name (&mut self) -> Result<String, Error> {
 		// Order names apparently just gives you the name of the pattern
 		let opt_string = get_string!{
 			openmpt_sys::openmpt_module_get_pattern_name(self.module.inner, self.num)
 		};
 
-		opt_string.expect("Got null pointer instead of string")
+		opt_string.ok_or(Error::NullString("pattern name"))
 	}
 
 	/// Get the number of rows for this pattern.
@@ -230,6 +299,53 @@ impl<'m> Pattern<'m> {
 			openmpt_sys::openmpt_module_get_pattern_num_rows(self.module.inner, self.num)
 		}
 	}
+
+	/// Get the rows per beat of this pattern.
+	///
+	/// ### Returns
+	/// The number of rows per beat (the secondary row highlight in OpenMPT),
+	/// or an error if the crate was built without the `libopenmpt-0-8` feature
+	/// or the linked libopenmpt is older than 0.8.
+	///
+	/// ### Remarks
+	/// Patterns without their own time signature return the module's default.
+	pub fn get_rows_per_beat(&mut self) -> Result<i32, Error> {
+		let (rows_per_beat, _) = self.get_time_signature()?;
+		if rows_per_beat > 0 { Ok(rows_per_beat) } else { Err(Error::OperationFailed("get pattern rows per beat")) }
+	}
+
+	/// Get the rows per measure of this pattern.
+	///
+	/// ### Returns
+	/// The number of rows per measure (the primary row highlight in OpenMPT),
+	/// or an error if the crate was built without the `libopenmpt-0-8` feature
+	/// or the linked libopenmpt is older than 0.8.
+	///
+	/// ### Remarks
+	/// Patterns without their own time signature return the module's default.
+	pub fn get_rows_per_measure(&mut self) -> Result<i32, Error> {
+		let (_, rows_per_measure) = self.get_time_signature()?;
+		if rows_per_measure > 0 { Ok(rows_per_measure) } else { Err(Error::OperationFailed("get pattern rows per measure")) }
+	}
+
+	// Rows per beat and rows per measure, as returned by libopenmpt
+	#[cfg(feature = "libopenmpt-0-8")]
+	fn get_time_signature(&mut self) -> Result<(i32, i32), Error> {
+		info::require_version(PATTERN_TIME_SIGNATURE_VERSION, "pattern time signatures")?;
+
+		unsafe {
+			Ok((
+				ffi::openmpt_module_get_pattern_rows_per_beat(self.module.inner, self.num),
+				ffi::openmpt_module_get_pattern_rows_per_measure(self.module.inner, self.num),
+			))
+		}
+	}
+
+	// Those functions are only linked when asked for, so that older libraries still load
+	#[cfg(not(feature = "libopenmpt-0-8"))]
+	fn get_time_signature(&mut self) -> Result<(i32, i32), Error> {
+		Err(Error::Unsupported("pattern time signatures"))
+	}
 }
 
 impl<'p, 'm> Row<'p, 'm> {
@@ -239,18 +355,58 @@ impl<'p, 'm> Row<'p, 'm> {
 	/// * `channel_num` : The index of the pattern channel at which the cell should be retrieved.
 	///
 	/// ### Returns
-	/// A Cell wrapper for the cell, or None if the channel doesn't exist.
-	pub fn get_cell_by_channel<'r> (&'r mut self, channel_num: i32) -> Option<Cell<'r, 'p, 'm>> {
-		assert!(self.num < self.pattern.get_num_rows());
-		assert!(self.num >= 0);
+	/// A Cell wrapper for the cell, or an error if the channel doesn't exist.
+	pub fn get_cell_by_channel<'r> (&'r mut self, channel_num: i32) -> Result<Cell<'r, 'p, 'm>, Error> {
+		let num_rows = self.pattern.get_num_rows();
+		Error::check_index("row", self.num, num_rows)?;
 
 		let num_channels = self.pattern.module.get_num_channels();
+		Error::check_index("channel", channel_num, num_channels)?;
 
-		if channel_num < 0 || channel_num >= num_channels {
-			None
-		} else {
-			Some(Cell{ row: self, channel_num: channel_num })
+		Ok(Cell{ row: self, channel_num })
+	}
+}
+
+impl<'p, 'm> Row<'p, 'm> {
+	/// Iterate over the cells of this row.
+	///
+	/// ### Returns
+	/// An iterator yielding the content of each cell, by channel order.
+	pub fn cells<'r> (&'r mut self) -> Cells<'r, 'p, 'm> {
+		let num_channels = self.pattern.module.get_num_channels();
+		Cells { row: self, next_channel: 0, num_channels }
+	}
+}
+
+impl<'p, 'm> Iterator for Rows<'p, 'm> {
+	type Item = Result<Vec<ModCommand>, String>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.next_row >= self.num_rows {
+			return None;
 		}
+
+		// In range by construction, no need to check it again
+		let mut row = Row { pattern: &mut *self.pattern, num: self.next_row };
+		self.next_row += 1;
+
+		Some(row.cells().collect())
+	}
+}
+
+impl<'r, 'p, 'm> Iterator for Cells<'r, 'p, 'm> {
+	type Item = Result<ModCommand, String>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.next_channel >= self.num_channels {
+			return None;
+		}
+
+		// In range by construction, no need to check it again
+		let mut cell = Cell { row: &mut *self.row, channel_num: self.next_channel };
+		self.next_channel += 1;
+
+		Some(cell.get_data())
 	}
 }
 
@@ -297,7 +453,7 @@ impl <'r, 'p, 'm> Cell<'r, 'p, 'm> {
 	///
 	/// ### Returns
 	/// The formatted pattern data for that cell.
-	pub fn get_formatted(&mut self, width: usize, pad: bool) -> String {
+	pub fn get_formatted(&mut self, width: usize, pad: bool) -> Result<String, Error> {
 		let opt_string = get_string!({
 			openmpt_sys::openmpt_module_format_pattern_row_channel(
 				self.row.pattern.module.inner,
@@ -309,7 +465,7 @@ impl <'r, 'p, 'm> Cell<'r, 'p, 'm> {
 			)
 		});
 
-		opt_string.expect("Got null pointer instead of string")
+		opt_string.ok_or(Error::NullString("formatted cell"))
 	}
 
 	/// Get formatted (human-readable) cell content.
@@ -319,7 +475,7 @@ impl <'r, 'p, 'm> Cell<'r, 'p, 'm> {
 	///
 	/// ### Returns
 	/// The formatted pattern data for that cell, at the given command index.
-	pub fn get_formatted_by_command(&mut self, command: ModuleCommandIndex) -> String {
+	pub fn get_formatted_by_command(&mut self, command: ModuleCommandIndex) -> Result<String, Error> {
 		let opt_string = get_string!({
 			openmpt_sys::openmpt_module_format_pattern_row_channel_command(
 				self.row.pattern.module.inner,
@@ -330,7 +486,7 @@ impl <'r, 'p, 'm> Cell<'r, 'p, 'm> {
 			)
 		});
 
-		opt_string.expect("Got null pointer instead of string")
+		opt_string.ok_or(Error::NullString("formatted cell"))
 	}
 
 	/// Get highlighting information for formatted cell content.
@@ -341,7 +497,7 @@ impl <'r, 'p, 'm> Cell<'r, 'p, 'm> {
 	///
 	/// ### Returns
 	/// The highlighting string for the formatted pattern data as retrieved by `get_formatted` for that cell.
-	pub fn get_highlight(&mut self, width: usize, pad: bool) -> String {
+	pub fn get_highlight(&mut self, width: usize, pad: bool) -> Result<String, Error> {
 		let opt_string = get_string!({
 			openmpt_sys::openmpt_module_highlight_pattern_row_channel(
 				self.row.pattern.module.inner,
@@ -353,7 +509,7 @@ impl <'r, 'p, 'm> Cell<'r, 'p, 'm> {
 			)
 		});
 
-		opt_string.expect("Got null pointer instead of string")
+		opt_string.ok_or(Error::NullString("cell highlight"))
 	}
 
 	/// Get highlighting information for formatted pattern content.
@@ -363,7 +519,7 @@ impl <'r, 'p, 'm> Cell<'r, 'p, 'm> {
 	///
 	/// ### Returns
 	/// The highlighting string for the formatted pattern data as retrieved by `get_formatted` for that cell, at the given command index.
-	pub fn get_highlight_by_command(&mut self, command: ModuleCommandIndex) -> String {
+	pub fn get_highlight_by_command(&mut self, command: ModuleCommandIndex) -> Result<String, Error> {
 		let opt_string = get_string!({
 			openmpt_sys::openmpt_module_highlight_pattern_row_channel_command(
 				self.row.pattern.module.inner,
@@ -374,7 +530,7 @@ impl <'r, 'p, 'm> Cell<'r, 'p, 'm> {
 			)
 		});
 
-		opt_string.expect("Got null pointer instead of string")
+		opt_string.ok_or(Error::NullString("cell highlight"))
 	}
 }
 
@@ -405,7 +561,10 @@ impl ModuleCommandIndex {
 
 #[cfg(test)]
 mod tests {
+	use super::*;
 	use super::super::test_helper;
+	use super::super::metadata::MetadataKey;
+	use mod_command::{EffectCommand, ModuleFormat, Note, SpecialNote};
 
 	#[test]
 	fn empty_module_list_names() {
@@ -414,31 +573,151 @@ mod tests {
 
 		if module.get_num_orders() > 0 {
 			let mut pattern = module.get_pattern_by_order(0).unwrap();
-			println!("Name of Pattern #0 : {:?}", pattern.get_name());
+			println!("Name of Pattern #0 : {:?}", pattern.get_name().unwrap());
 		}
 
 		if module.get_num_channels() > 0 {
-			println!("Name of Channel #0 : {:?}", module.get_channel_name(0));
+			println!("Name of Channel #0 : {:?}", module.get_channel_name(0).unwrap());
 		}
 
 		if module.get_num_instruments() > 0 {
-			println!("Name of Instrument #0 : {:?}", module.get_instrument_name(0));
+			println!("Name of Instrument #0 : {:?}", module.get_instrument_name(0).unwrap());
 		}
 
 		if module.get_num_samples() > 0 {
-			println!("Name of Sample #0 : {:?}", module.get_sample_name(0));
+			println!("Name of Sample #0 : {:?}", module.get_sample_name(0).unwrap());
 		}
 
 		if module.get_num_subsongs() > 0 {
-			println!("Name of Subsong #0 : {:?}", module.get_subsong_name(0));
+			println!("Name of Subsong #0 : {:?}", module.get_subsong_name(0).unwrap());
 		}
 	}
 
+	#[test]
+	fn out_of_range_names_are_errors() {
+		let mut module = test_helper::load_file_as_module("empty_module.xm").unwrap();
+
+		let num_instruments = module.get_num_instruments();
+		let num_samples = module.get_num_samples();
+		let num_channels = module.get_num_channels();
+		let num_subsongs = module.get_num_subsongs();
+
+		assert_eq!(module.get_instrument_name(num_instruments),
+			Err(Error::IndexOutOfRange { item: "instrument", index: num_instruments, count: num_instruments }));
+		assert!(module.get_sample_name(num_samples).is_err());
+		assert!(module.get_channel_name(num_channels).is_err());
+		assert!(module.get_subsong_name(num_subsongs).is_err());
+
+		assert!(module.get_instrument_name(-1).is_err());
+		assert!(module.get_sample_name(-1).is_err());
+		assert!(module.get_channel_name(-1).is_err());
+		assert!(module.get_subsong_name(-1).is_err());
+	}
+
+	#[test]
+	fn out_of_range_rows_and_cells_are_errors() {
+		let mut module = test_helper::load_file_as_module("UNATCO.it").unwrap();
+		let num_channels = module.get_num_channels();
+		let mut pattern = module.get_pattern_by_number(0).unwrap();
+		let num_rows = pattern.get_num_rows();
+
+		assert!(pattern.get_row_by_number(-1).is_err());
+		assert!(pattern.get_row_by_number(num_rows).is_err());
+
+		let mut row = pattern.get_row_by_number(0).unwrap();
+		assert!(row.get_cell_by_channel(-1).is_err());
+		assert!(row.get_cell_by_channel(num_channels).is_err());
+	}
+
+	#[test]
+	#[cfg(not(feature = "libopenmpt-0-8"))]
+	fn time_signatures_need_feature() {
+		let mut module = test_helper::load_file_as_module("UNATCO.it").unwrap();
+		assert_eq!(module.get_pattern_time_signature(0), Default::default());
+
+		let mut pattern = module.get_pattern_by_number(0).unwrap();
+		assert_eq!(pattern.get_rows_per_beat(), Err(Error::Unsupported("pattern time signatures")));
+		assert_eq!(pattern.get_rows_per_measure(), Err(Error::Unsupported("pattern time signatures")));
+	}
+
 	#[test]
 	fn unatco_iterative_reading() {
 		iterative_reading("UNATCO.it");
 	}
 
+	#[test]
+	fn unatco_effect_strings_match_openmpt() {
+		effect_strings_match_openmpt("UNATCO.it");
+	}
+
+	#[test]
+	fn empty_module_effect_strings_match_openmpt() {
+		effect_strings_match_openmpt("empty_module.xm");
+	}
+
+	fn effect_strings_match_openmpt(file_name : &str) {
+		let mut module = test_helper::load_file_as_module(file_name).unwrap();
+		let type_ext = module.get_metadata(MetadataKey::TypeExt).unwrap();
+		let format = ModuleFormat::from_type_ext(&type_ext).unwrap();
+		let num_patterns = module.get_num_patterns();
+		let num_channels = module.get_num_channels();
+
+		for pattern_num in 0..num_patterns {
+			let mut pattern = module.get_pattern_by_number(pattern_num).unwrap();
+			let num_rows = pattern.get_num_rows();
+
+			for row_num in 0..num_rows {
+				let mut row = pattern.get_row_by_number(row_num).unwrap();
+
+				for channel_num in 0..num_channels {
+					let mut cell = row.get_cell_by_channel(channel_num).unwrap();
+					let data = cell.get_data().unwrap();
+
+					match (data.note, data.command) {
+						// The effect column holds a plugin parameter value instead
+						(Note::Special(SpecialNote::ParamControl), _) => continue,
+						(Note::Special(SpecialNote::ParamControlSmooth), _) => continue,
+						(_, EffectCommand::None) => continue,
+						(_, command) => {
+							let mut expected = cell.get_formatted_by_command(ModuleCommandIndex::Effect).unwrap();
+							expected.push_str(&cell.get_formatted_by_command(ModuleCommandIndex::Parameter).unwrap());
+							assert_eq!(command.to_effect_string(format), expected);
+						},
+					}
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn unatco_iterators_match_indexed_access() {
+		let mut module = test_helper::load_file_as_module("UNATCO.it").unwrap();
+		let num_channels = module.get_num_channels();
+		let orders: Vec<i32> = module.orders().collect();
+
+		assert_eq!(orders.len() as i32, module.get_num_orders());
+
+		for (order_num, pattern_num) in orders.into_iter().enumerate() {
+			let mut pattern = match module.get_pattern_by_number(pattern_num) {
+				Some(pattern) => pattern,
+				None => continue, // Separator or skip order
+			};
+			let num_rows = pattern.get_num_rows();
+			let rows: Vec<Vec<ModCommand>> = pattern.rows().map(|row| row.unwrap()).collect();
+
+			assert_eq!(rows.len() as i32, num_rows, "Row count mismatch at order #{}", order_num);
+
+			for (row_num, row_data) in rows.iter().enumerate() {
+				let mut row = pattern.get_row_by_number(row_num as i32).unwrap();
+				assert_eq!(row_data.len() as i32, num_channels);
+
+				for (channel_num, cell_data) in row.cells().enumerate() {
+					assert_eq!(&cell_data.unwrap(), &row_data[channel_num]);
+				}
+			}
+		}
+	}
+
 	fn iterative_reading(file_name : &str) {
 		let mut module = test_helper::load_file_as_module(file_name).unwrap();
 		let num_orders = module.get_num_orders();
@@ -459,7 +738,7 @@ mod tests {
 					assert!(cell.get_data().is_ok());
 
 					if channel_num != 0 { row_string.push_str("|"); }
-					row_string.push_str(cell.get_formatted(0, false).as_str());
+					row_string.push_str(cell.get_formatted(0, false).unwrap().as_str());
 				}
 				//println!("{}", row_string);
 			}
@@ -2,8 +2,12 @@
 
 use super::Module;
 use openmpt_sys;
+use error::Error;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum MetadataKey {
 	/// Module format extension (e.g. it)
 	TypeExt,
@@ -27,6 +31,150 @@ pub enum MetadataKey {
 	SongMessageOrInstruments,
 	/// A list of warnings that were generated while loading the module.
 	LoadWarnings,
+	/// Extension of the format the module was converted from, if any (e.g. mod for a MOD file loaded as XM)
+	OriginalTypeExt,
+	/// Tracker name associated with the format the module was converted from, if any
+	OriginalTypeName,
+	/// Song message, falling back to instrument and sample names only if they look like they contain a message.
+	/// Requires a recent libopenmpt.
+	SongMessageHeuristic,
+	/// Any other key, as reported by `Module::get_metadata_keys`
+	Other(String),
+}
+
+// Keys left out of ModuleMetadata::extra : those that have a dedicated field,
+// and message_raw, which has none but is a variant of the song message
+const STRUCT_KEYS:[MetadataKey; 11] = [
+	MetadataKey::TypeExt,
+	MetadataKey::TypeName,
+	MetadataKey::ContainerExt,
+	MetadataKey::ContainerName,
+	MetadataKey::ModuleTitle,
+	MetadataKey::ModuleArtist,
+	MetadataKey::ModuleTracker,
+	MetadataKey::ModuleSaveDate,
+	MetadataKey::SongMessage,
+	MetadataKey::SongMessageOrInstruments,
+	MetadataKey::LoadWarnings,
+];
+
+/// All of a module's metadata, parsed, obtained with `Module::metadata`.
+///
+/// Fields that are not set or not supported by the module format are left empty.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize))]
+pub struct ModuleMetadata {
+	/// Module format extension (e.g. it)
+	pub type_ext: String,
+	/// Tracker name associated with the module format (e.g. Impulse Tracker)
+	pub type_name: String,
+	/// Container format the module file is embedded in, if any
+	pub container: Option<ContainerInfo>,
+	/// Module title
+	pub title: String,
+	/// Author of the module
+	pub artist: String,
+	/// Tracker that was (most likely) used to save the module file, if known
+	pub tracker: String,
+	/// Date the module was last saved, if known
+	pub save_date: Option<ModuleDate>,
+	/// Song message
+	pub message: String,
+	/// Warnings that were generated while loading the module, one per entry
+	pub warnings: Vec<String>,
+	/// Any other key reported by `get_metadata_keys`, along with its value
+	/// (e.g. `originaltype`). `message_raw` is left out, in favor of `message`.
+	/// Empty if libopenmpt fails to list the keys.
+	pub extra: BTreeMap<String, String>,
+}
+
+/// The container a module file is embedded in.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize))]
+pub struct ContainerInfo {
+	/// Container format extension (e.g. umx)
+	pub ext: String,
+	/// Full container name (e.g. Unreal Music)
+	pub name: String,
+}
+
+/// A date in ISO-8601 format, as used by `MetadataKey::ModuleSaveDate`.
+///
+/// Module formats store dates with varying precision,
+/// so everything but the year is optional.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize))]
+pub struct ModuleDate {
+	pub year: u16,
+	pub month: Option<u8>,
+	pub day: Option<u8>,
+	pub hour: Option<u8>,
+	pub minute: Option<u8>,
+	pub second: Option<u8>,
+}
+
+impl FromStr for ModuleDate {
+	type Err = String;
+
+	/// Parse a (possibly shortened) ISO-8601 date, such as
+	/// `2018-03-09`, `2018-03-09T22:45` or `2018-03-09T22:45:30Z`.
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let invalid = || format!("Invalid date \"{}\"", s);
+
+		let s_trimmed = s.trim_end_matches('Z');
+		let mut date_time = s_trimmed.splitn(2, 'T');
+		let date = date_time.next().unwrap_or("");
+		let time = date_time.next();
+
+		let mut date_parts = date.split('-');
+		let year = date_parts.next().and_then(|y| u16::from_str(y).ok()).ok_or_else(invalid)?;
+
+		let parse_part = |part: Option<&str>, max: u8| -> Result<Option<u8>, String> {
+			match part {
+				None => Ok(None),
+				Some(part) => match u8::from_str(part) {
+					Ok(value) if part.len() == 2 && value <= max => Ok(Some(value)),
+					_ => Err(invalid()),
+				},
+			}
+		};
+
+		let month = parse_part(date_parts.next(), 12)?;
+		let day = parse_part(date_parts.next(), 31)?;
+
+		let (hour, minute, second) = match time {
+			Some(time) => {
+				let mut time_parts = time.split(':');
+				let hour = parse_part(time_parts.next(), 23)?;
+				let minute = parse_part(time_parts.next(), 59)?;
+				let second = parse_part(time_parts.next(), 60)?;
+				if time_parts.next().is_some() { return Err(invalid()); }
+				(hour, minute, second)
+			},
+			None => (None, None, None),
+		};
+
+		if date_parts.next().is_some() || month == Some(0) || day == Some(0) || (time.is_some() && day.is_none()) {
+			return Err(invalid());
+		}
+
+		Ok(ModuleDate { year, month, day, hour, minute, second })
+	}
+}
+
+impl fmt::Display for ModuleDate {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{:04}", self.year)?;
+
+		for (separator, part) in [('-', self.month), ('-', self.day), ('T', self.hour), (':', self.minute), (':', self.second)].iter() {
+			match *part {
+				Some(value) => write!(f, "{}{:02}", separator, value)?,
+				None => break,
+			}
+		}
+
+		Ok(())
+	}
 }
 
 impl MetadataKey {
@@ -44,10 +192,43 @@ impl MetadataKey {
 			SongMessage => "message",
 			SongMessageOrInstruments => "message_raw",
 			LoadWarnings => "warnings",
+			OriginalTypeExt => "originaltype",
+			OriginalTypeName => "originaltype_long",
+			SongMessageHeuristic => "message_heuristic",
+			Other(ref key) => key,
 		}
 	}
 }
 
+impl<'a> From<&'a str> for MetadataKey {
+	fn from(key: &'a str) -> MetadataKey {
+		use self::MetadataKey::*;
+		match key {
+			"type" => TypeExt,
+			"type_long" => TypeName,
+			"container" => ContainerExt,
+			"container_long" => ContainerName,
+			"title" => ModuleTitle,
+			"artist" => ModuleArtist,
+			"tracker" => ModuleTracker,
+			"date" => ModuleSaveDate,
+			"message" => SongMessage,
+			"message_raw" => SongMessageOrInstruments,
+			"warnings" => LoadWarnings,
+			"originaltype" => OriginalTypeExt,
+			"originaltype_long" => OriginalTypeName,
+			"message_heuristic" => SongMessageHeuristic,
+			other => Other(other.to_owned()),
+		}
+	}
+}
+
+impl fmt::Display for MetadataKey {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(self.to_str())
+	}
+}
+
 impl Module {
 	/// Get a metadata item value.
 	///
@@ -55,23 +236,96 @@ impl Module {
 	/// * `key` : Metadata item key to query, from the `MetadataKey` enum.
 	///
 	/// ### Returns
-	/// The associated value for key, or None in case of error.
-	pub fn get_metadata(&mut self, key : MetadataKey) -> Option<String> {
-		let key = key.to_str();
-		get_string_with_string! (key, {
+	/// The associated value for key, or an error if the key contains a NUL byte
+	/// or libopenmpt returns no value.
+	pub fn get_metadata(&mut self, key : MetadataKey) -> Result<String, Error> {
+		self.get_metadata_by_name(key.to_str())
+	}
+
+	/// Get all metadata at once.
+	///
+	/// ### Returns
+	/// The module's metadata, with the save date and load warnings parsed,
+	/// along with the value of any key that does not have its own field.
+	pub fn metadata(&mut self) -> ModuleMetadata {
+		let container_ext = self.get_metadata(MetadataKey::ContainerExt).unwrap_or_default();
+		let container = if container_ext.is_empty() {
+			None
+		} else {
+			Some(ContainerInfo {
+				ext: container_ext,
+				name: self.get_metadata(MetadataKey::ContainerName).unwrap_or_default(),
+			})
+		};
+
+		let save_date = self.get_metadata(MetadataKey::ModuleSaveDate).ok()
+			.and_then(|date| ModuleDate::from_str(&date).ok());
+
+		let warnings = self.get_metadata(MetadataKey::LoadWarnings).unwrap_or_default()
+			.lines()
+			.filter(|line| !line.is_empty())
+			.map(|line| line.to_owned())
+			.collect();
+
+		let mut extra = BTreeMap::new();
+
+		for key in self.get_metadata_keys().unwrap_or_default() {
+			if STRUCT_KEYS.contains(&key) {
+				continue;
+			}
+
+			if let Ok(value) = self.get_metadata_by_name(key.to_str()) {
+				extra.insert(key.to_string(), value);
+			}
+		}
+
+		ModuleMetadata {
+			type_ext: self.get_metadata(MetadataKey::TypeExt).unwrap_or_default(),
+			type_name: self.get_metadata(MetadataKey::TypeName).unwrap_or_default(),
+			container,
+			title: self.get_metadata(MetadataKey::ModuleTitle).unwrap_or_default(),
+			artist: self.get_metadata(MetadataKey::ModuleArtist).unwrap_or_default(),
+			tracker: self.get_metadata(MetadataKey::ModuleTracker).unwrap_or_default(),
+			save_date,
+			message: self.get_metadata(MetadataKey::SongMessage).unwrap_or_default(),
+			warnings,
+			extra,
+		}
+	}
+
+	fn get_metadata_by_name(&mut self, key : &str) -> Result<String, Error> {
+		if key.contains('\0') {
+			return Err(Error::InteriorNul(key.to_owned()));
+		}
+
+		let opt_string = get_string_with_string! (key, {
 			openmpt_sys::openmpt_module_get_metadata(self.inner, key)
-		})
+		});
+
+		opt_string.ok_or(Error::NullString("metadata value"))
 	}
 
-	pub fn get_metadata_keys(&mut self) -> String {
+	/// Get the metadata keys supported by the module and the libopenmpt version in use.
+	///
+	/// ### Returns
+	/// The list of available keys, with keys unknown to `MetadataKey` as `MetadataKey::Other`,
+	/// or an error if libopenmpt fails to return them.
+	pub fn get_metadata_keys(&mut self) -> Result<Vec<MetadataKey>, Error> {
 		let opt_string = get_string! {
 			openmpt_sys::openmpt_module_get_metadata_keys(self.inner)
 		};
 
-		opt_string.expect("Got null pointer instead of string")
+		opt_string.map(|keys| parse_metadata_keys(&keys)).ok_or(Error::NullString("metadata keys"))
 	}
 }
 
+fn parse_metadata_keys(keys: &str) -> Vec<MetadataKey> {
+	keys.split(';')
+		.filter(|key| !key.is_empty())
+		.map(MetadataKey::from)
+		.collect()
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -84,21 +338,88 @@ mod tests {
 		assert_eq!(module.get_metadata(MetadataKey::TypeName).unwrap(), "FastTracker II");
 	}
 
+	#[test]
+	fn keys_with_nul_bytes_are_rejected() {
+		let mut module = test_helper::load_file_as_module("empty_module.xm").unwrap();
+		let key = MetadataKey::Other("type\0long".to_owned());
+
+		assert_eq!(module.get_metadata(key), Err(Error::InteriorNul("type\0long".to_owned())));
+	}
+
+	#[test]
+	fn dummy_file_metadata_is_parsed() {
+		let mut module = test_helper::load_file_as_module("empty_module.xm").unwrap();
+		let metadata = module.metadata();
+
+		assert_eq!(metadata.type_ext, "xm");
+		assert_eq!(metadata.type_name, "FastTracker II");
+		assert_eq!(metadata.container, None);
+		assert!(metadata.warnings.iter().all(|warning| !warning.is_empty()));
+
+		for key in metadata.extra.keys() {
+			assert!(!STRUCT_KEYS.contains(&MetadataKey::from(key.as_str())));
+		}
+	}
+
+	#[test]
+	fn metadata_keys_are_parsed() {
+		let keys = parse_metadata_keys("type;type_long;originaltype;message_heuristic;some_future_key;");
+
+		assert_eq!(keys, vec![
+			MetadataKey::TypeExt,
+			MetadataKey::TypeName,
+			MetadataKey::OriginalTypeExt,
+			MetadataKey::SongMessageHeuristic,
+			MetadataKey::Other("some_future_key".to_owned()),
+		]);
+		assert_eq!(keys[4].to_str(), "some_future_key");
+		assert!(parse_metadata_keys("").is_empty());
+	}
+
+	#[test]
+	fn metadata_keys_round_trip() {
+		for key in STRUCT_KEYS.iter().chain(&[MetadataKey::OriginalTypeExt, MetadataKey::OriginalTypeName, MetadataKey::SongMessageHeuristic]) {
+			assert_eq!(&MetadataKey::from(key.to_str()), key);
+		}
+	}
+
+	#[test]
+	fn dates_are_parsed() {
+		let date = ModuleDate::from_str("2018-03-09T22:45:30Z").unwrap();
+		assert_eq!(date, ModuleDate { year: 2018, month: Some(3), day: Some(9), hour: Some(22), minute: Some(45), second: Some(30) });
+		assert_eq!(date.to_string(), "2018-03-09T22:45:30");
+
+		let date = ModuleDate::from_str("1997-12-01T08:10").unwrap();
+		assert_eq!((date.hour, date.minute, date.second), (Some(8), Some(10), None));
+		assert_eq!(date.to_string(), "1997-12-01T08:10");
+
+		let date = ModuleDate::from_str("1995-06").unwrap();
+		assert_eq!((date.year, date.month, date.day), (1995, Some(6), None));
+		assert_eq!(date.to_string(), "1995-06");
+	}
+
+	#[test]
+	fn invalid_dates_are_rejected() {
+		for date in &["", "T", "year", "1995-13", "1995-00-01", "1995-1-1", "1995-01-01T25:00", "1995-01-01-01", "1995T10:00", "1995-01-01T10:00:00:00"] {
+			assert!(ModuleDate::from_str(date).is_err(), "{:?} should not parse", date);
+		}
+	}
+
 	#[test]
 	fn all_known_metadata_keys_are_supported() {
 		let mut module = test_helper::load_file_as_module("empty_module.xm").unwrap();
-		let keys = module.get_metadata_keys();
+		let keys = module.get_metadata_keys().unwrap();
 		
-		assert!(keys.contains(MetadataKey::TypeExt.to_str()));
-		assert!(keys.contains(MetadataKey::TypeName.to_str()));
-		assert!(keys.contains(MetadataKey::ContainerExt.to_str()));
-		assert!(keys.contains(MetadataKey::ContainerName.to_str()));
-		assert!(keys.contains(MetadataKey::ModuleTitle.to_str()));
-		assert!(keys.contains(MetadataKey::ModuleArtist.to_str()));
-		assert!(keys.contains(MetadataKey::ModuleTracker.to_str()));
-		assert!(keys.contains(MetadataKey::ModuleSaveDate.to_str()));
-		assert!(keys.contains(MetadataKey::SongMessage.to_str()));
-		assert!(keys.contains(MetadataKey::SongMessageOrInstruments.to_str()));
-		assert!(keys.contains(MetadataKey::LoadWarnings.to_str()));
+		assert!(keys.contains(&MetadataKey::TypeExt));
+		assert!(keys.contains(&MetadataKey::TypeName));
+		assert!(keys.contains(&MetadataKey::ContainerExt));
+		assert!(keys.contains(&MetadataKey::ContainerName));
+		assert!(keys.contains(&MetadataKey::ModuleTitle));
+		assert!(keys.contains(&MetadataKey::ModuleArtist));
+		assert!(keys.contains(&MetadataKey::ModuleTracker));
+		assert!(keys.contains(&MetadataKey::ModuleSaveDate));
+		assert!(keys.contains(&MetadataKey::SongMessage));
+		assert!(keys.contains(&MetadataKey::SongMessageOrInstruments));
+		assert!(keys.contains(&MetadataKey::LoadWarnings));
 	}
 }
\ No newline at end of file
@@ -4,6 +4,9 @@
 //! This contains everything needed to create instances Modules
 //! using module files or data. The rest is defined in the various submodules.
 //!
+//! Modules are always created through the libopenmpt_ext API,
+//! so that the extension interfaces wrapped in `interactive` can be queried later on.
+//!
 //! # Thread-safety
 //! This wrapper is written on top of the C API, which the openmpt developpers
 //! reccomand against attempting to access concurrently, unlike with the C++
@@ -18,6 +21,8 @@
 
 
 use openmpt_sys;
+use ffi;
+use std::ffi::CString;
 use std::os::raw::*;
 use std::ptr;
 
@@ -27,16 +32,27 @@ pub mod iteration;
 pub mod render;
 pub mod stream;
 pub mod current;
-#[cfg(test)] mod test_helper;
+pub mod interactive;
+pub mod snapshot;
+pub mod inventory;
+pub mod highlight;
+pub mod now_playing;
+pub mod timing;
+pub mod beats;
+pub mod notes;
+#[cfg(test)] pub(crate) mod test_helper;
 
 pub struct Module {
 	inner : *mut openmpt_sys::openmpt_module,
+	// Owns `inner`, needed to query the libopenmpt_ext interfaces
+	ext : *mut ffi::openmpt_module_ext,
 }
 
 impl Drop for Module {
 	fn drop(&mut self) {
 		unsafe {
-			openmpt_sys::openmpt_module_destroy(self.inner);
+			// Also destroys the underlying openmpt_module
+			ffi::openmpt_module_ext_destroy(self.ext);
 		}
 	}
 }
@@ -50,33 +66,30 @@ impl Module {
 	/// * `init_ctls` : A list of initial ctl values, see the `ctls` module.
 	///
 	/// ### Returns
-	/// The constructed openmpt_module, or None on failure.
+	/// The constructed openmpt_module, or None on failure,
+	/// including when one of the initial ctls is invalid or could not be applied.
 	///
 	/// ### Remarks
 	/// The input data can be discarded after a Module has been constructed successfully.
 	pub fn create_from_memory(buffer : &[u8], logger : Logger, init_ctls : &[ctls::Ctl]) -> Result<Module, ()> {
-		let module_ptr = unsafe {
-			openmpt_sys::openmpt_module_create_from_memory(
+		// Passed to libopenmpt so that load.* ctls apply while loading
+		let initial_ctls = InitialCtls::new(init_ctls)?;
+
+		let ext_ptr = unsafe {
+			ffi::openmpt_module_ext_create_from_memory(
 				buffer.as_ptr() as *const _,
 				buffer.len(),
 				logger.log_func(),
-				ptr::null_mut(), // user (As unsafe as it gets! Not touching this.)
-				ptr::null() // init_ctls (Setting those manually below.)
+				ptr::null_mut(), // loguser (As unsafe as it gets! Not touching this.)
+				None, // errfunc
+				ptr::null_mut(), // erruser
+				ptr::null_mut(), // error
+				ptr::null_mut(), // error_message
+				initial_ctls.as_ptr()
 			)
 		};
 
-		if module_ptr.is_null() {
-			return Err(())
-		}
-		
-		let mut module = Module { inner : module_ptr };
-
-		// Set each init ctl by hand, lists of stucts of FFI string pointers are too much of a nightmare to deal with in Rust
-		for init_ctl in init_ctls {
-			module.enum_ctl_set(init_ctl);
-		}
-
-		Ok(module)
+		Module::from_ext(ext_ptr)?.with_init_ctls(init_ctls)
 	}
 
 	/// Construct an openmpt_module from a stream.
@@ -87,39 +100,95 @@ impl Module {
 	/// * `init_ctls` : A list of initial ctl values, see the `ctls` module.
 	///
 	/// ### Returns
-	/// The constructed openmpt_module, or None on failure.
+	/// The constructed openmpt_module, or None on failure,
+	/// including when one of the initial ctls is invalid or could not be applied.
 	///
 	/// ### Remarks
 	/// The input data can be discarded after a Module has been constructed successfully.
 	pub fn create<T : stream::ModuleStream>(stream : &mut T, logger : Logger, init_ctls : &[ctls::Ctl]) -> Result<Module, ()> {
 		let stream_ptr:*mut T = stream;
+		let initial_ctls = InitialCtls::new(init_ctls)?;
 		
-		let module_ptr = unsafe {
-			openmpt_sys::openmpt_module_create(
+		let ext_ptr = unsafe {
+			ffi::openmpt_module_ext_create(
 				T::get_file_callbacks(),
 				stream_ptr as *mut _,
 				logger.log_func(),
-				ptr::null_mut(), // user (As unsafe as it gets! Not touching this.)
-				ptr::null() // init_ctls (Setting those manually below.)
+				ptr::null_mut(), // loguser (As unsafe as it gets! Not touching this.)
+				None, // errfunc
+				ptr::null_mut(), // erruser
+				ptr::null_mut(), // error
+				ptr::null_mut(), // error_message
+				initial_ctls.as_ptr()
 			)
 		};
 
+		Module::from_ext(ext_ptr)?.with_init_ctls(init_ctls)
+	}
+
+	fn from_ext(ext_ptr : *mut ffi::openmpt_module_ext) -> Result<Module, ()> {
+		if ext_ptr.is_null() {
+			return Err(())
+		}
+
+		let module_ptr = unsafe {
+			ffi::openmpt_module_ext_get_module(ext_ptr)
+		};
+
 		if module_ptr.is_null() {
+			unsafe { ffi::openmpt_module_ext_destroy(ext_ptr); }
 			return Err(())
 		}
 
-		let mut module = Module { inner : module_ptr };
+		Ok(Module { inner : module_ptr, ext : ext_ptr })
+	}
 
-		// Set each init ctl by hand, lists of stucts of FFI string pointers are too much of a nightmare to deal with in Rust
+	// Set each init ctl again, in case loading the module reset any of them,
+	// and make sure libopenmpt applied them
+	fn with_init_ctls(mut self, init_ctls : &[ctls::Ctl]) -> Result<Module, ()> {
 		for init_ctl in init_ctls {
-			module.enum_ctl_set(init_ctl);
+			self.validated_ctl_set(init_ctl).map_err(|_| ())?;
 		}
 
-		Ok(module)
+		Ok(self)
+	}
+}
+
+// A null-terminated list of initial ctls, along with the strings it points to
+struct InitialCtls {
+	_strings: Vec<CString>,
+	list: Vec<openmpt_sys::openmpt_module_initial_ctl>,
+}
+
+impl InitialCtls {
+	fn new(init_ctls : &[ctls::Ctl]) -> Result<InitialCtls, ()> {
+		let mut strings = Vec::with_capacity(init_ctls.len() * 2);
+		let mut list = Vec::with_capacity(init_ctls.len() + 1);
+
+		for init_ctl in init_ctls {
+			init_ctl.validate().map_err(|_| ())?;
+
+			let key = CString::new(init_ctl.key_to_str()).unwrap();
+			let value = CString::new(init_ctl.param_to_str()).unwrap();
+
+			// The CString buffers stay in place when moved into the Vec
+			list.push(openmpt_sys::openmpt_module_initial_ctl { ctl: key.as_ptr(), value: value.as_ptr() });
+			strings.push(key);
+			strings.push(value);
+		}
+
+		list.push(openmpt_sys::openmpt_module_initial_ctl::default());
+
+		Ok(InitialCtls { _strings: strings, list })
+	}
+
+	fn as_ptr(&self) -> *const openmpt_sys::openmpt_module_initial_ctl {
+		self.list.as_ptr()
 	}
 }
 
 /// An enum containing the key effort values for `could_open_propability`
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum CouldOpenEffort {
 	/// Does not even look at stream at all
 	NoEffort,
@@ -172,6 +241,8 @@ impl Logger {
 /// Probability between 0.0 and 1.0.
 ///
 /// ### Remarks
+/// The stream is read from its current position, and is not rewound afterwards.
+///
 /// Can return any value between 0.0 and 1.0. Only 0.0 and 1.0 are definitive answers,
 /// all values in between are just estimates. In general, any return value >0.0
 /// means that you should try loading the file, and any value below 1.0 means that loading may fail.
@@ -183,7 +254,7 @@ pub fn could_open_propability<T : stream::ModuleStream> (stream : &mut T, effort
 	let stream_ptr:*mut T = stream;
 
 	unsafe {
-		openmpt_sys::openmpt_could_open_propability(openmpt_sys::openmpt_stream_callbacks::default(), stream_ptr as *mut _, effort.value(), logger.log_func(), ptr::null_mut())
+		openmpt_sys::openmpt_could_open_propability(T::get_file_callbacks(), stream_ptr as *mut _, effort.value(), logger.log_func(), ptr::null_mut())
 	}
 }
 
@@ -213,6 +284,44 @@ mod tests {
 		assert!(load_complete_prob == 0.0);
 	}
 
+	#[test]
+	fn initial_ctl_list_is_null_terminated() {
+		let initial_ctls = InitialCtls::new(&[ctls::Ctl::SkipLoadingSamples(true), ctls::Ctl::PlaybackTempoFactor(2.0)]).unwrap();
+
+		assert_eq!(initial_ctls.list.len(), 3);
+		assert_eq!(unsafe { ::std::ffi::CStr::from_ptr(initial_ctls.list[0].ctl) }.to_str(), Ok("load.skip_samples"));
+		assert_eq!(unsafe { ::std::ffi::CStr::from_ptr(initial_ctls.list[1].value) }.to_str(), Ok("2"));
+		assert!(initial_ctls.list[2].ctl.is_null() && initial_ctls.list[2].value.is_null());
+
+		assert!(InitialCtls::new(&[ctls::Ctl::PlaybackTempoFactor(0.0)]).is_err());
+	}
+
+	#[test]
+	fn initial_ctls_apply_while_loading() {
+		let data = test_helper::build_test_mod();
+		let render = |init_ctls: &[ctls::Ctl]| {
+			let mut module = Module::create_from_memory(&data, Logger::None, init_ctls).unwrap();
+			let mut buffer = vec![0i16; 4800];
+			module.read_mono(48000, &mut buffer);
+			buffer
+		};
+
+		// Skipping samples only has an effect if set before the module is loaded
+		assert!(render(&[]).iter().any(|&sample| sample != 0));
+		assert!(render(&[ctls::Ctl::SkipLoadingSamples(true)]).iter().all(|&sample| sample == 0));
+	}
+
+	#[test]
+	fn could_open_reads_the_stream() {
+		let data = test_helper::read_file("empty_module.xm");
+
+		let header_prob = could_open_propability(&mut Cursor::new(&data), CouldOpenEffort::VerifyHeader, Logger::None);
+		let load_complete_prob = could_open_propability(&mut Cursor::new(&data), CouldOpenEffort::LoadCompleteModule, Logger::None);
+
+		assert!(header_prob >= 0.5);
+		assert_eq!(load_complete_prob, 1.0);
+	}
+
 	#[test]
 	fn text_file_fails_to_load() {
 		let mut module = test_helper::load_file_as_module("Cargo.toml");
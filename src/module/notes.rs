@@ -0,0 +1,356 @@
+//! Definitions for extracting every note played by a module,
+//! with the time at which it starts and ends.
+//!
+//! Rows are taken from a timing map, in the order they are played, and their
+//! cells are read from a pattern snapshot. Timing within a row is derived from
+//! the measured length of the row and its speed (ticks per row), which is what
+//! note delays, note cuts and delayed key-offs are expressed in.
+
+use super::Module;
+use super::snapshot::PatternData;
+use super::timing::TimingMap;
+use super::super::mod_command::{ModCommand, Note, SpecialNote, Pitch, VolumeCommand, EffectCommand, ExtendedCommand, ModExtendedCommand, S3MExtendedCommand};
+use error::Error;
+use std::os::raw::*;
+
+/// What ended a note.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoteEndReason {
+	/// Another note was started on the same channel
+	NextNote,
+	/// Key-off note (`===`) or key-off effect
+	KeyOff,
+	/// Note fade (`~~~`)
+	Fade,
+	/// Note cut (`^^^`) or note cut effect
+	Cut,
+}
+
+/// When and how a note ended.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoteEnd {
+	/// Time at which the note ended, in seconds from the start of the song
+	pub time_seconds: c_double,
+	/// Time at which the note ended, in frames from the start of the song
+	pub frame: u64,
+	pub reason: NoteEndReason,
+}
+
+/// A single note-on event.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoteEvent {
+	/// Time at which the note starts, note delays included, in seconds from the start of the song
+	pub time_seconds: c_double,
+	/// Time at which the note starts, note delays included, in frames from the start of the song
+	pub frame: u64,
+	pub order: i32,
+	pub row: i32,
+	pub channel: i32,
+	/// The instrument (or sample) playing the note, taken from earlier
+	/// rows if the cell has none. 0 if no instrument was ever set on the channel.
+	pub instrument: u8,
+	pub pitch: Pitch,
+	/// Volume set in the volume column or with the volume effect,
+	/// None if the instrument's default volume is used.
+	pub volume: Option<u8>,
+	/// The effect in the cell of the note
+	pub effect: EffectCommand,
+	/// When and how the note ended, None if it was still playing at the end of the song
+	pub end: Option<NoteEnd>,
+}
+
+// Tick-based effects found in a single cell
+#[derive(Debug, Default)]
+struct CellTicks {
+	delay: u32,
+	cut: Option<u32>,
+	key_off: Option<u32>,
+}
+
+impl CellTicks {
+	fn of(cell: &ModCommand) -> CellTicks {
+		let mut ticks = CellTicks::default();
+
+		match cell.command {
+			EffectCommand::KeyOff(param) => ticks.key_off = Some(u32::from(param)),
+			EffectCommand::DelayCut(delay, cut) => {
+				ticks.delay = u32::from(delay);
+				ticks.cut = Some(u32::from(delay) + u32::from(cut));
+			},
+			_ => match cell.command.extended() {
+				Some(ExtendedCommand::Mod(ModExtendedCommand::NoteDelay(param))) |
+				Some(ExtendedCommand::S3M(S3MExtendedCommand::NoteDelay(param))) => ticks.delay = u32::from(param),
+				Some(ExtendedCommand::Mod(ModExtendedCommand::NoteCut(param))) |
+				Some(ExtendedCommand::S3M(S3MExtendedCommand::NoteCut(param))) => ticks.cut = Some(u32::from(param)),
+				_ => {},
+			},
+		}
+
+		ticks
+	}
+}
+
+fn pattern_delay(cell: &ModCommand) -> u32 {
+	match cell.command.extended() {
+		Some(ExtendedCommand::Mod(ModExtendedCommand::PatternDelay(param))) |
+		Some(ExtendedCommand::S3M(S3MExtendedCommand::PatternDelay(param))) => u32::from(param),
+		_ => 0,
+	}
+}
+
+// Tone portamento slides the playing note towards the new one instead of starting it
+fn is_tone_portamento(cell: &ModCommand) -> bool {
+	matches!(cell.command, EffectCommand::TonePortamento(_) | EffectCommand::TonePortaVol(_, _))
+		|| matches!(cell.volcmd, VolumeCommand::TonePortamento(_))
+}
+
+fn cell_volume(cell: &ModCommand) -> Option<u8> {
+	match (cell.volcmd, cell.command) {
+		(VolumeCommand::Volume(volume), _) => Some(volume),
+		(_, EffectCommand::Volume(volume)) => Some(volume),
+		_ => None,
+	}
+}
+
+impl PatternData {
+	/// Extract every note-on event, in the order given by a timing map.
+	///
+	/// ### Parameters
+	/// * `map` : The timing map of the module this snapshot was taken from.
+	///
+	/// ### Returns
+	/// Every note started during playback, sorted by start time.
+	///
+	/// ### Remarks
+	/// Like `playback_rows`, this is an approximation of what libopenmpt does :
+	/// * Notes delayed past the end of their row are never played, and cuts or
+	///   key-offs scheduled past the end of their row are ignored.
+	/// * Notes with a tone portamento slide the playing note, and are not note-on events.
+	/// * Instruments with a sustain loop may keep playing a while after a key-off or fade,
+	///   and sample-based notes may stop on their own long before they are ended.
+	pub fn note_events(&self, map: &TimingMap) -> Vec<NoteEvent> {
+		let num_channels = self.num_channels.max(0) as usize;
+		let sample_rate = c_double::from(map.sample_rate);
+
+		let mut events: Vec<NoteEvent> = Vec::new();
+		let mut playing: Vec<Option<usize>> = vec![None; num_channels];
+		let mut instruments: Vec<u8> = vec![0; num_channels];
+
+		let end_note = |events: &mut Vec<NoteEvent>, index: Option<usize>, frame: u64, reason: NoteEndReason| {
+			if let Some(event) = index.and_then(|index| events.get_mut(index)) {
+				if event.end.is_none() {
+					event.end = Some(NoteEnd { time_seconds: frame as c_double / sample_rate, frame, reason });
+				}
+			}
+		};
+
+		for run in map.row_runs() {
+			let first = &map.rows[run.start];
+			let cells = match self.get_pattern_by_number(first.pattern).and_then(|pattern| pattern.rows.get(first.row as usize)) {
+				Some(cells) => cells,
+				None => continue,
+			};
+
+			// A delayed row has one entry per pass when the map could tell them apart,
+			// and a single entry for all of them otherwise (see `Module::timing_map`).
+			// Passes after the first don't trigger the row's notes again.
+			let passes = cells.iter().map(pattern_delay).max().unwrap_or(0) as usize + 1;
+			let entries_per_play = if run.len() % passes == 0 { passes } else { run.len() };
+
+			for play_start in run.clone().step_by(entries_per_play) {
+				let timing = &map.rows[play_start];
+				let end_frame = map.entry_frame(play_start + entries_per_play);
+				let speed = timing.speed.max(1) as u32;
+				let tick_frames = end_frame.saturating_sub(timing.frame) as c_double / (c_double::from(speed) * passes as c_double);
+				let tick_frame = |tick: u32| timing.frame + (c_double::from(tick) * tick_frames).round() as u64;
+
+				for (channel_num, cell) in cells.iter().enumerate().take(num_channels) {
+					let ticks = CellTicks::of(cell);
+
+					if cell.instr != 0 {
+						instruments[channel_num] = cell.instr;
+					}
+
+					if ticks.delay >= speed {
+						continue;
+					}
+
+					let start_frame = tick_frame(ticks.delay);
+
+					match cell.note {
+						Note::Note(pitch) if !is_tone_portamento(cell) => {
+							end_note(&mut events, playing[channel_num], start_frame, NoteEndReason::NextNote);
+							playing[channel_num] = Some(events.len());
+
+							events.push(NoteEvent {
+								time_seconds: start_frame as c_double / sample_rate,
+								frame: start_frame,
+								order: timing.order,
+								row: timing.row,
+								channel: channel_num as i32,
+								instrument: instruments[channel_num],
+								pitch,
+								volume: cell_volume(cell),
+								effect: cell.command,
+								end: None,
+							});
+						},
+						Note::Special(SpecialNote::KeyOff) => end_note(&mut events, playing[channel_num], start_frame, NoteEndReason::KeyOff),
+						Note::Special(SpecialNote::Fade) => end_note(&mut events, playing[channel_num], start_frame, NoteEndReason::Fade),
+						Note::Special(SpecialNote::NoteCut) => end_note(&mut events, playing[channel_num], start_frame, NoteEndReason::Cut),
+						_ => {},
+					}
+
+					if let Some(cut) = ticks.cut.filter(|&cut| cut < speed) {
+						end_note(&mut events, playing[channel_num], tick_frame(cut), NoteEndReason::Cut);
+					}
+					if let Some(key_off) = ticks.key_off.filter(|&key_off| key_off < speed) {
+						end_note(&mut events, playing[channel_num], tick_frame(key_off), NoteEndReason::KeyOff);
+					}
+				}
+			}
+		}
+
+		// Note delays can start notes out of channel order
+		events.sort_by_key(|event| event.frame);
+		events
+	}
+}
+
+impl Module {
+	/// Extract every note-on event of the current sub-song.
+	///
+	/// ### Parameters
+	/// * `map` : The timing map of the song, as returned by `timing_map`.
+	///
+	/// ### Returns
+	/// Every note started during playback, sorted by start time, or an error
+	/// if the pattern data could not be read (see `snapshot_patterns`).
+	///
+	/// ### Remarks
+	/// See `PatternData::note_events` for the limitations of the extraction.
+	pub fn note_events(&mut self, map: &TimingMap) -> Result<Vec<NoteEvent>, Error> {
+		let snapshot = self.snapshot_patterns()?;
+		Ok(snapshot.note_events(map))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::Logger;
+	use super::super::snapshot::PatternSnapshot;
+	use super::super::test_helper;
+
+	fn cell(note: Note, instr: u8, command: EffectCommand) -> ModCommand {
+		ModCommand { note, instr, volcmd: VolumeCommand::None, command }
+	}
+
+	fn note(value: u8) -> Note {
+		Note::Note(Pitch::from_note_value(value).unwrap())
+	}
+
+	// 2 channels, speed 6, 600 frames per row (100 frames per tick) at 1000 Hz
+	fn test_data(rows: Vec<Vec<ModCommand>>) -> (PatternData, TimingMap) {
+		let positions: Vec<(i32, i32)> = (0..rows.len() as i32).map(|row| (0, row)).collect();
+		test_data_at(rows, &positions)
+	}
+
+	fn test_data_at(rows: Vec<Vec<ModCommand>>, positions: &[(i32, i32)]) -> (PatternData, TimingMap) {
+		let data = PatternData {
+			num_channels: 2,
+			patterns: vec![PatternSnapshot { name: String::new(), rows }],
+			orders: vec![0],
+		};
+
+		(data, test_helper::build_timing_map(positions, 600))
+	}
+
+	#[test]
+	fn notes_are_delayed_and_ended() {
+		let empty = cell(Note::None, 0, EffectCommand::None);
+		let mut first = cell(note(61), 1, EffectCommand::None);
+		first.volcmd = VolumeCommand::Volume(32);
+
+		let (data, map) = test_data(vec![
+			vec![first, cell(note(63), 2, EffectCommand::S3MCmdEX(0xD, 3))],
+			vec![cell(note(65), 0, EffectCommand::S3MCmdEX(0xC, 2)), cell(Note::Special(SpecialNote::KeyOff), 0, EffectCommand::None)],
+			vec![cell(note(66), 0, EffectCommand::TonePortamento(4)), cell(note(68), 2, EffectCommand::S3MCmdEX(0xD, 7))],
+			vec![cell(Note::Special(SpecialNote::NoteCut), 0, EffectCommand::None), empty],
+		]);
+
+		let events = data.note_events(&map);
+		let summary: Vec<(u64, i32, u8, u8)> = events.iter()
+			.map(|event| (event.frame, event.channel, event.instrument, event.pitch.value()))
+			.collect();
+
+		assert_eq!(summary, vec![(0, 0, 1, 61), (300, 1, 2, 63), (600, 0, 1, 65)]);
+		assert_eq!(events[0].volume, Some(32));
+		assert_eq!(events[1].volume, None);
+		assert_eq!(events[1].time_seconds, 0.3);
+
+		let ends: Vec<(u64, NoteEndReason)> = events.iter()
+			.map(|event| event.end.map(|end| (end.frame, end.reason)).unwrap())
+			.collect();
+		assert_eq!(ends, vec![(600, NoteEndReason::NextNote), (600, NoteEndReason::KeyOff), (800, NoteEndReason::Cut)]);
+	}
+
+	#[test]
+	fn pattern_delays_shorten_ticks() {
+		let (data, map) = test_data(vec![
+			vec![cell(note(61), 1, EffectCommand::ModCmdEX(0xE, 1)), cell(note(61), 1, EffectCommand::KeyOff(3))],
+		]);
+
+		let events = data.note_events(&map);
+
+		assert_eq!(events.len(), 2);
+		assert_eq!(events[0].end, None);
+		assert_eq!(events[1].end.unwrap().frame, 150);
+	}
+
+	#[test]
+	fn delayed_rows_are_played_once() {
+		// The delayed row has one entry per pass, 100 frames per tick each
+		let (data, map) = test_data_at(vec![
+			vec![cell(note(61), 1, EffectCommand::None), cell(Note::None, 0, EffectCommand::None)],
+			vec![cell(note(63), 1, EffectCommand::ModCmdEX(0xE, 2)), cell(note(65), 1, EffectCommand::KeyOff(3))],
+			vec![cell(Note::Special(SpecialNote::NoteCut), 0, EffectCommand::None), cell(Note::None, 0, EffectCommand::None)],
+		], &[(0, 0), (0, 1), (0, 1), (0, 1), (0, 2)]);
+
+		let events = data.note_events(&map);
+		let summary: Vec<(u64, i32, i32)> = events.iter().map(|event| (event.frame, event.row, event.channel)).collect();
+
+		assert_eq!(summary, vec![(0, 0, 0), (600, 1, 0), (600, 1, 1)]);
+		assert_eq!(events[1].end.map(|end| (end.frame, end.reason)), Some((2400, NoteEndReason::Cut)));
+		assert_eq!(events[2].end.map(|end| (end.frame, end.reason)), Some((900, NoteEndReason::KeyOff)));
+	}
+
+	#[test]
+	fn delayed_module_rows_are_played_once() {
+		// Pattern delay (EE2) with a new note on the second row, playing it 3 times
+		let mut data = test_helper::build_test_mod();
+		let cell = 1084 + 16;
+		data[cell..cell + 4].copy_from_slice(&[0x00, 0xD6, 0x1E, 0xE2]);
+
+		let mut module = Module::create_from_memory(&data, Logger::None, &[]).unwrap();
+		let map = module.timing_map(48000);
+		let events = module.note_events(&map).unwrap();
+
+		let rows: Vec<i32> = events.iter().map(|event| event.row).collect();
+		assert_eq!(rows, vec![0, 1]);
+		assert_eq!(events[1].frame, map.first_occurrence(0, 1).unwrap().frame);
+		assert_eq!(events[1].end, None);
+	}
+
+	#[test]
+	fn unatco_note_events() {
+		let mut module = test_helper::load_file_as_module("UNATCO.it").unwrap();
+		let map = module.timing_map(48000);
+		let events = module.note_events(&map).unwrap();
+
+		assert!(!events.is_empty());
+		assert!(events.windows(2).all(|pair| pair[0].frame <= pair[1].frame));
+		assert!(events.iter().all(|event| event.frame < map.total_frames));
+		assert!(events.iter().filter_map(|event| event.end).all(|end| end.frame <= map.total_frames));
+	}
+}
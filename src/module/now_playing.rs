@@ -0,0 +1,223 @@
+//! Following the playback position while a module is being rendered,
+//! for tracker-style views that scroll pattern rows along with the audio.
+//!
+//! libopenmpt only reports the position reached by the renderer, which runs ahead
+//! of what can be heard by however many frames are buffered by the audio output.
+//! `NowPlaying` remembers the position after each rendered block, so that the
+//! position being heard can be looked up given the current output latency.
+
+use super::Module;
+use super::highlight::PatternTextOptions;
+use error::Error;
+use std::collections::{HashMap, VecDeque};
+
+/// A position in the module, as reported by libopenmpt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PlaybackPosition {
+	pub order: i32,
+	pub pattern: i32,
+	pub row: i32,
+}
+
+impl PlaybackPosition {
+	/// Get the position the module is currently being rendered at.
+	pub fn current(module: &mut Module) -> PlaybackPosition {
+		PlaybackPosition {
+			order: module.get_current_order(),
+			pattern: module.get_current_pattern(),
+			row: module.get_current_row(),
+		}
+	}
+}
+
+/// The row being heard, along with its text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NowPlayingRow {
+	pub position: PlaybackPosition,
+	/// The row rendered with `Module::render_pattern_row`
+	pub text: String,
+}
+
+/// Tracks the playback position of a module being rendered in blocks.
+///
+/// Call `update` before rendering starts and after every block, then
+/// `position` or `now_playing` whenever the view should be refreshed.
+///
+/// ### Remarks
+/// Positions are only sampled between blocks, so a row change is placed at the end of
+/// the block it happened in. Smaller blocks give more accurate timing.
+#[derive(Debug, Clone)]
+pub struct NowPlaying {
+	options: PatternTextOptions,
+	max_latency_frames: u64,
+	frames_rendered: u64,
+	// Frame at which each position was first seen, oldest first
+	history: VecDeque<(u64, PlaybackPosition)>,
+	row_text: HashMap<(i32, i32), String>,
+}
+
+impl NowPlaying {
+	/// Create a new tracker.
+	///
+	/// ### Parameters
+	/// * `options` : How to render the text of the rows.
+	/// * `max_latency_frames` : The largest number of frames that can be buffered
+	///   downstream. Positions older than that are discarded.
+	pub fn new(options: PatternTextOptions, max_latency_frames: u64) -> NowPlaying {
+		NowPlaying {
+			options,
+			max_latency_frames,
+			frames_rendered: 0,
+			history: VecDeque::new(),
+			row_text: HashMap::new(),
+		}
+	}
+
+	/// Record the position of the module after rendering a block.
+	///
+	/// ### Parameters
+	/// * `module` : The module being rendered.
+	/// * `frames` : Number of frames returned by the last `read_*` call, 0 before rendering starts.
+	pub fn update(&mut self, module: &mut Module, frames: usize) {
+		let position = PlaybackPosition::current(module);
+		self.record_position(position, frames);
+	}
+
+	/// Record a position reached after rendering a block, as `update` does.
+	pub fn record_position(&mut self, position: PlaybackPosition, frames: usize) {
+		self.frames_rendered += frames as u64;
+
+		if self.history.back().map(|&(_, last)| last) != Some(position) {
+			self.history.push_back((self.frames_rendered, position));
+		}
+
+		// Keep the last position that started before the oldest audible frame
+		let oldest_audible = self.frames_rendered.saturating_sub(self.max_latency_frames);
+		while self.history.len() > 1 && self.history[1].0 <= oldest_audible {
+			self.history.pop_front();
+		}
+	}
+
+	/// Total number of frames recorded so far.
+	pub fn frames_rendered(&self) -> u64 {
+		self.frames_rendered
+	}
+
+	/// Get the position being heard.
+	///
+	/// ### Parameters
+	/// * `buffered_frames` : Number of rendered frames not yet played by the audio output.
+	///
+	/// ### Returns
+	/// The position, or None if nothing was recorded yet.
+	pub fn position(&self, buffered_frames: u64) -> Option<PlaybackPosition> {
+		let audible_frame = self.frames_rendered.saturating_sub(buffered_frames);
+
+		self.history.iter().rev()
+			.find(|&&(frame, _)| frame <= audible_frame)
+			.or_else(|| self.history.front())
+			.map(|&(_, position)| position)
+	}
+
+	/// Get the position being heard, along with the text of its row.
+	///
+	/// ### Parameters
+	/// * `module` : The module being rendered.
+	/// * `buffered_frames` : Number of rendered frames not yet played by the audio output.
+	///
+	/// ### Returns
+	/// The row, None if nothing was recorded yet, or an error if the row could not be rendered.
+	///
+	/// ### Remarks
+	/// Row texts are cached, call `clear_text_cache` if the pattern data changes.
+	pub fn now_playing(&mut self, module: &mut Module, buffered_frames: u64) -> Result<Option<NowPlayingRow>, Error> {
+		let position = match self.position(buffered_frames) {
+			Some(position) => position,
+			None => return Ok(None),
+		};
+
+		let key = (position.pattern, position.row);
+		if !self.row_text.contains_key(&key) {
+			let text = module.render_pattern_row(position.pattern, position.row, &self.options)?;
+			self.row_text.insert(key, text);
+		}
+
+		Ok(Some(NowPlayingRow { position, text: self.row_text[&key].clone() }))
+	}
+
+	/// Forget the cached row texts.
+	pub fn clear_text_cache(&mut self) {
+		self.row_text.clear();
+	}
+
+	/// Forget all recorded positions, after seeking for example.
+	pub fn reset(&mut self) {
+		self.frames_rendered = 0;
+		self.history.clear();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::highlight::HighlightStyle;
+	use super::super::test_helper;
+
+	fn position(order: i32, row: i32) -> PlaybackPosition {
+		PlaybackPosition { order, pattern: order, row }
+	}
+
+	#[test]
+	fn position_is_latency_compensated() {
+		let mut now_playing = NowPlaying::new(PatternTextOptions::default(), 10000);
+		assert_eq!(now_playing.position(0), None);
+
+		now_playing.record_position(position(0, 0), 0);
+		now_playing.record_position(position(0, 1), 1000);
+		now_playing.record_position(position(0, 1), 1000);
+		now_playing.record_position(position(1, 0), 1000);
+
+		assert_eq!(now_playing.frames_rendered(), 3000);
+		assert_eq!(now_playing.position(0), Some(position(1, 0)));
+		assert_eq!(now_playing.position(1000), Some(position(0, 1)));
+		assert_eq!(now_playing.position(2500), Some(position(0, 0)));
+		assert_eq!(now_playing.position(50000), Some(position(0, 0)));
+	}
+
+	#[test]
+	fn old_positions_are_discarded() {
+		let mut now_playing = NowPlaying::new(PatternTextOptions::default(), 1500);
+
+		for row in 0..10 {
+			now_playing.record_position(position(0, row), 1000);
+		}
+
+		assert_eq!(now_playing.history.len(), 3);
+		assert_eq!(now_playing.position(1500), Some(position(0, 7)));
+		assert_eq!(now_playing.position(1000), Some(position(0, 8)));
+
+		now_playing.reset();
+		assert_eq!(now_playing.position(0), None);
+	}
+
+	#[test]
+	fn now_playing_follows_rendering() {
+		let mut module = test_helper::load_file_as_module("UNATCO.it").unwrap();
+		let options = PatternTextOptions { style: HighlightStyle::Plain, ..PatternTextOptions::default() };
+		let mut now_playing = NowPlaying::new(options.clone(), 48000);
+		let mut buffer = vec![0i16; 2 * 1024];
+
+		now_playing.update(&mut module, 0);
+		for _ in 0..100 {
+			let frames = module.read_interleaved_stereo(48000, &mut buffer);
+			now_playing.update(&mut module, frames);
+		}
+
+		let current = now_playing.now_playing(&mut module, 0).unwrap().unwrap();
+		assert_eq!(current.position, PlaybackPosition::current(&mut module));
+		assert_eq!(current.text, module.render_pattern_row(current.position.pattern, current.position.row, &options).unwrap());
+
+		let delayed = now_playing.position(48000).unwrap();
+		assert_ne!(delayed, current.position);
+	}
+}
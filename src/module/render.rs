@@ -14,15 +14,31 @@
 //! that range though and thus might overshoot.
 
 use super::Module;
+use super::ctls::AtEndBehavior;
+use error::Error;
+use ffi;
 use openmpt_sys;
 use std::os::raw::*;
 use std::cmp::min;
+use std::str::FromStr;
 
 const RENDER_MASTERGAIN_MILLIBEL:c_int = 1;
 const RENDER_STEREOSEPARATION_PERCENT:c_int = 2;
 const RENDER_INTERPOLATIONFILTER_LENGTH:c_int = 3;
 const RENDER_VOLUMERAMPING_STRENGTH:c_int = 4;
 
+/// Outcome of a call to one of the rendering methods, obtained with `Module::read_status`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReadStatus {
+	/// Some audio frames were rendered.
+	Rendered(usize),
+	/// Nothing was rendered because the end of the song was reached,
+	/// and the `play.at_end` behavior in effect stopped rendering.
+	Ended(AtEndBehavior),
+	/// Nothing was rendered because the buffer could not hold a single frame.
+	EmptyBuffer,
+}
+
 impl Module {
 	/// Render audio data.
 	///
@@ -192,6 +208,53 @@ impl Module {
 		}
 	}
 
+	/// Interpret the number of frames returned by one of the rendering methods.
+	///
+	/// ### Parameters
+	/// * `frames` : The number of frames returned by the last rendering call.
+	/// * `buffer_frames` : The number of frames the buffers passed to that call could hold.
+	///
+	/// ### Returns
+	/// `ReadStatus::Rendered` if any frame was rendered, `ReadStatus::EmptyBuffer` if
+	/// `buffer_frames` is 0, or `ReadStatus::Ended` along with the end of song behavior
+	/// (see `ctl_set_play_at_end`) that caused rendering to stop.
+	///
+	/// Returns an error if libopenmpt reported one instead of reaching the end of the
+	/// song, or if the `play.at_end` ctl can't be read.
+	///
+	/// ### Remarks
+	/// With `AtEndBehavior::Continue`, the next rendering call resumes playback from the loop start.
+	///
+	/// libopenmpt keeps its last error until it is cleared, so an error left
+	/// by an earlier call is also reported here. It is cleared afterwards.
+	pub fn read_status(&mut self, frames: usize, buffer_frames: usize) -> Result<ReadStatus, Error> {
+		if frames > 0 {
+			return Ok(ReadStatus::Rendered(frames));
+		}
+
+		if buffer_frames == 0 {
+			return Ok(ReadStatus::EmptyBuffer);
+		}
+
+		let failed = unsafe {
+			let failed = ffi::openmpt_module_error_get_last(self.inner) != ffi::ERROR_OK;
+			ffi::openmpt_module_error_clear(self.inner);
+			failed
+		};
+
+		if failed {
+			return Err(Error::OperationFailed("render audio"));
+		}
+
+		let at_end = self.ctl_get_text("play.at_end")?;
+		let behavior = AtEndBehavior::from_str(&at_end).map_err(|reason| Error::InvalidCtl {
+			key: "play.at_end".to_owned(),
+			reason: reason.to_owned(),
+		})?;
+
+		Ok(ReadStatus::Ended(behavior))
+	}
+
 	/// Set Repeat Count.
 	///
 	/// ### Parameters
@@ -355,8 +418,58 @@ impl Module {
 
 #[cfg(test)]
 mod tests {
+	use super::*;
+	use super::super::Logger;
+	use super::super::ctls::Ctl;
 	use super::super::test_helper;
 
+	fn render_to_end(module: &mut Module) -> ReadStatus {
+		let mut buffer = vec![0i16; 48000];
+
+		loop {
+			let frames = module.read_mono(48000, &mut buffer);
+			match module.read_status(frames, buffer.len()).unwrap() {
+				ReadStatus::Rendered(_) => continue,
+				status => return status,
+			}
+		}
+	}
+
+	#[test]
+	fn end_of_song_behavior_is_reported() {
+		for &behavior in &[AtEndBehavior::Fadeout, AtEndBehavior::Continue, AtEndBehavior::Stop] {
+			let mut module = Module::create_from_memory(&test_helper::build_test_mod(), Logger::None, &[Ctl::AtEnd(behavior)]).unwrap();
+
+			assert_eq!(module.ctl_get_play_at_end().unwrap(), behavior);
+			assert_eq!(render_to_end(&mut module), ReadStatus::Ended(behavior));
+		}
+	}
+
+	#[test]
+	fn empty_buffers_are_not_the_end() {
+		let mut module = Module::create_from_memory(&test_helper::build_test_mod(), Logger::None, &[]).unwrap();
+		let mut buffer = vec![0i16; 1];
+
+		// Too small for a single stereo frame
+		let frames = module.read_interleaved_stereo(48000, &mut buffer);
+		assert_eq!(module.read_status(frames, buffer.len() / 2), Ok(ReadStatus::EmptyBuffer));
+		assert_eq!(module.read_status(1, 1), Ok(ReadStatus::Rendered(1)));
+	}
+
+	#[test]
+	fn rendering_resumes_after_end_with_continue() {
+		let mut module = Module::create_from_memory(&test_helper::build_test_mod(), Logger::None, &[]).unwrap();
+		let mut buffer = vec![0i16; 4800];
+
+		module.ctl_set_play_at_end(AtEndBehavior::Continue).unwrap();
+		assert_eq!(render_to_end(&mut module), ReadStatus::Ended(AtEndBehavior::Continue));
+		assert!(module.read_mono(48000, &mut buffer) > 0);
+
+		module.ctl_set_play_at_end(AtEndBehavior::Stop).unwrap();
+		assert_eq!(render_to_end(&mut module), ReadStatus::Ended(AtEndBehavior::Stop));
+		assert_eq!(module.read_mono(48000, &mut buffer), 0);
+	}
+
 	#[test]
 	fn dummy_file_opens_with_default_render_parameters() {
 		let mut module = test_helper::load_file_as_module("empty_module.xm").unwrap();
@@ -0,0 +1,458 @@
+//! Definitions for owned copies of the module's pattern data,
+//! which can be kept around and shared between threads
+//! independently of the `Module` they were extracted from.
+
+use openmpt_sys;
+use super::Module;
+use super::super::mod_command::{ModCommand, EffectCommand, ExtendedCommand, ModExtendedCommand, S3MExtendedCommand};
+use error::Error;
+use std::collections::HashSet;
+
+// Special pattern numbers from OpenMPT's soundlib/Snd_defs.h
+const ORDER_SKIP:i32 = 0xFFFE; // "+++" separator, skipped during playback
+
+/// An owned, immutable copy of all the patterns of a module,
+/// obtained with `Module::snapshot_patterns`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatternData {
+	/// The number of pattern channels, which every row contains.
+	pub num_channels: i32,
+	/// The patterns of the module, indexed by pattern number.
+	pub patterns: Vec<PatternSnapshot>,
+	/// The pattern number found at each order position of the current sequence.
+	pub orders: Vec<i32>,
+}
+
+/// An owned, immutable copy of a single pattern.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatternSnapshot {
+	/// The pattern name.
+	pub name: String,
+	/// The pattern cells, indexed by row, then by channel.
+	pub rows: Vec<Vec<ModCommand>>,
+}
+
+impl PatternData {
+	/// Get pattern by index.
+	///
+	/// ### Returns
+	/// The pattern, or None if no such pattern exists.
+	pub fn get_pattern_by_number(&self, pattern_num: i32) -> Option<&PatternSnapshot> {
+		if pattern_num < 0 { return None; }
+		self.patterns.get(pattern_num as usize)
+	}
+
+	/// Get pattern at order position.
+	///
+	/// ### Returns
+	/// The pattern found at the given order position of the current sequence,
+	/// or None if no such pattern exists (including separator and skip orders).
+	pub fn get_pattern_by_order(&self, order_num: i32) -> Option<&PatternSnapshot> {
+		if order_num < 0 { return None; }
+		self.orders.get(order_num as usize).and_then(|&pattern_num| self.get_pattern_by_number(pattern_num))
+	}
+}
+
+/// Iterator over the rows of a module in the order they are played,
+/// returned by `PatternData::playback_rows`.
+///
+/// Yields `(order, pattern, row)` for each row reached during playback, following
+/// position jumps, pattern breaks and pattern loops, and stops at the end of the
+/// sequence or as soon as the song would start looping.
+///
+/// ### Remarks
+/// This is only an approximation of what libopenmpt does when playing the module,
+/// which also depends on the format, the tracker used, compatibility flags, etc.
+/// Pattern delays do not repeat rows, since they only make them last longer.
+pub struct PlaybackRows<'d> {
+	data: &'d PatternData,
+	order: i32,
+	row: i32,
+	loop_start: Vec<i32>,
+	loop_count: Vec<u8>,
+	visited: HashSet<(i32, i32)>,
+	finished: bool,
+}
+
+impl PatternData {
+	/// Iterate over rows in playback order.
+	///
+	/// ### Returns
+	/// An iterator yielding `(order, pattern, row)` for every row played,
+	/// starting from the first order of the current sequence.
+	pub fn playback_rows<'d> (&'d self) -> PlaybackRows<'d> {
+		let num_channels = self.num_channels.max(0) as usize;
+
+		PlaybackRows {
+			data: self,
+			order: 0,
+			row: 0,
+			loop_start: vec![0; num_channels],
+			loop_count: vec![0; num_channels],
+			visited: HashSet::new(),
+			finished: false,
+		}
+	}
+}
+
+impl<'d> PlaybackRows<'d> {
+	fn enter_order(&mut self, order: i32, row: i32) {
+		self.order = order;
+		self.row = row;
+
+		for start in &mut self.loop_start { *start = 0; }
+		for count in &mut self.loop_count { *count = 0; }
+	}
+
+	// Skips over separators and empty patterns, returns None at the end of the sequence.
+	fn current_pattern(&mut self) -> Option<(i32, &'d PatternSnapshot)> {
+		let data = self.data;
+
+		loop {
+			if self.order < 0 || self.order as usize >= data.orders.len() {
+				return None;
+			}
+
+			let pattern_num = data.orders[self.order as usize];
+
+			match data.get_pattern_by_number(pattern_num) {
+				Some(pattern) if pattern.get_num_rows() > 0 => {
+					// Breaking past the last row lands on the first one
+					if self.row >= pattern.get_num_rows() { self.row = 0; }
+					return Some((pattern_num, pattern));
+				},
+				Some(_) => {},
+				None if pattern_num == ORDER_SKIP => {},
+				None => return None, // "---" end of song marker
+			}
+
+			let next_order = self.order + 1;
+			self.enter_order(next_order, 0);
+		}
+	}
+}
+
+impl<'d> Iterator for PlaybackRows<'d> {
+	type Item = (i32, i32, i32);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.finished {
+			return None;
+		}
+
+		let (pattern_num, pattern) = match self.current_pattern() {
+			Some(found) => found,
+			None => {
+				self.finished = true;
+				return None;
+			},
+		};
+
+		let (order, row) = (self.order, self.row);
+		let looping = self.loop_count.iter().any(|&count| count > 0);
+
+		// Rows can only be played twice from within a pattern loop, anything else means the song restarts
+		if !self.visited.insert((order, row)) && !looping {
+			self.finished = true;
+			return None;
+		}
+
+		let mut jump_order = None;
+		let mut break_row = None;
+		let mut loop_to = None;
+
+		for (channel_num, cell) in pattern.rows[row as usize].iter().enumerate() {
+			let loop_param = match cell.command {
+				EffectCommand::PositionJump(param) => { jump_order = Some(i32::from(param)); continue },
+				EffectCommand::PatternBreak(param) => { break_row = Some(i32::from(param)); continue },
+				_ => match cell.command.extended() {
+					Some(ExtendedCommand::Mod(ModExtendedCommand::PatternLoop(param))) => param,
+					Some(ExtendedCommand::S3M(S3MExtendedCommand::PatternLoop(param))) => param,
+					_ => continue,
+				},
+			};
+
+			if channel_num >= self.loop_count.len() {
+				continue;
+			}
+
+			if loop_param == 0 {
+				self.loop_start[channel_num] = row;
+			} else if self.loop_count[channel_num] == 0 {
+				self.loop_count[channel_num] = loop_param;
+				loop_to = Some(self.loop_start[channel_num]);
+			} else {
+				self.loop_count[channel_num] -= 1;
+				if self.loop_count[channel_num] > 0 {
+					loop_to = Some(self.loop_start[channel_num]);
+				}
+			}
+		}
+
+		if let Some(loop_start) = loop_to {
+			self.row = loop_start;
+		} else if jump_order.is_some() || break_row.is_some() {
+			self.enter_order(jump_order.unwrap_or(order + 1), break_row.unwrap_or(0));
+		} else if row + 1 < pattern.get_num_rows() {
+			self.row = row + 1;
+		} else {
+			self.enter_order(order + 1, 0);
+		}
+
+		Some((order, pattern_num, row))
+	}
+}
+
+impl PatternSnapshot {
+	/// Get the number of rows for this pattern.
+	pub fn get_num_rows(&self) -> i32 {
+		self.rows.len() as i32
+	}
+
+	/// Get pattern cell by row and channel.
+	///
+	/// ### Returns
+	/// The cell data, or None if the row or the channel doesn't exist.
+	pub fn get_cell(&self, row_num: i32, channel_num: i32) -> Option<&ModCommand> {
+		if row_num < 0 || channel_num < 0 { return None; }
+		self.rows.get(row_num as usize).and_then(|row| row.get(channel_num as usize))
+	}
+}
+
+impl Module {
+	/// Extract all of the module's pattern data at once.
+	///
+	/// ### Returns
+	/// An owned copy of every pattern, or an error if one of the cells
+	/// has an invalid note, or if libopenmpt fails to return a pattern name.
+	///
+	/// ### Remarks
+	/// Pattern data never changes during playback, so the snapshot stays valid
+	/// for as long as the module exists, and can be read while it is being rendered.
+	pub fn snapshot_patterns(&mut self) -> Result<PatternData, Error> {
+		let num_channels = self.get_num_channels();
+		let num_patterns = self.get_num_patterns();
+		let num_orders = self.get_num_orders();
+
+		let mut patterns = Vec::with_capacity(num_patterns as usize);
+
+		for pattern_num in 0..num_patterns {
+			let mut pattern = match self.get_pattern_by_number(pattern_num) {
+				Some(pattern) => pattern,
+				None => return Err(Error::IndexOutOfRange { item: "pattern", index: pattern_num, count: num_patterns }),
+			};
+
+			let num_rows = pattern.get_num_rows();
+			let mut rows = Vec::with_capacity(num_rows as usize);
+
+			for row_num in 0..num_rows {
+				let mut row = pattern.get_row_by_number(row_num)?;
+				let mut cells = Vec::with_capacity(num_channels as usize);
+
+				for channel_num in 0..num_channels {
+					let mut cell = row.get_cell_by_channel(channel_num)?;
+					let data = cell.get_data().map_err(|reason| {
+						Error::InvalidCell { pattern: pattern_num, row: row_num, channel: channel_num, reason }
+					})?;
+					cells.push(data);
+				}
+
+				rows.push(cells);
+			}
+
+			patterns.push(PatternSnapshot {
+				name: pattern.get_name()?,
+				rows,
+			});
+		}
+
+		let orders = (0..num_orders).map(|order_num| unsafe {
+			openmpt_sys::openmpt_module_get_order_pattern(self.inner, order_num)
+		}).collect();
+
+		Ok(PatternData {
+			num_channels,
+			patterns,
+			orders,
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::test_helper;
+	use std::thread;
+
+	fn assert_send_sync_clone<T: Send + Sync + Clone>() {}
+
+	#[test]
+	fn pattern_data_can_be_shared() {
+		assert_send_sync_clone::<PatternData>();
+	}
+
+	#[test]
+	fn unatco_snapshot_matches_module() {
+		let mut module = test_helper::load_file_as_module("UNATCO.it").unwrap();
+		let snapshot = module.snapshot_patterns().unwrap();
+
+		assert_eq!(snapshot.num_channels, module.get_num_channels());
+		assert_eq!(snapshot.patterns.len() as i32, module.get_num_patterns());
+		assert_eq!(snapshot.orders.len() as i32, module.get_num_orders());
+
+		for (pattern_num, pattern_snapshot) in snapshot.patterns.iter().enumerate() {
+			let mut pattern = module.get_pattern_by_number(pattern_num as i32).unwrap();
+			assert_eq!(pattern_snapshot.get_num_rows(), pattern.get_num_rows());
+			assert_eq!(pattern_snapshot.name, pattern.get_name().unwrap());
+
+			for row_num in 0..pattern_snapshot.get_num_rows() {
+				let mut row = pattern.get_row_by_number(row_num).unwrap();
+
+				for channel_num in 0..snapshot.num_channels {
+					let mut cell = row.get_cell_by_channel(channel_num).unwrap();
+					assert_eq!(pattern_snapshot.get_cell(row_num, channel_num), Some(&cell.get_data().unwrap()));
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn snapshot_can_be_read_from_another_thread() {
+		let mut module = test_helper::load_file_as_module("UNATCO.it").unwrap();
+		let snapshot = module.snapshot_patterns().unwrap();
+		let expected_rows: i32 = snapshot.patterns.iter().map(|p| p.get_num_rows()).sum();
+
+		let total_rows = thread::spawn(move || {
+			snapshot.patterns.iter().map(|p| p.get_num_rows()).sum::<i32>()
+		}).join().unwrap();
+
+		assert_eq!(total_rows, expected_rows);
+	}
+
+	fn empty_pattern(num_rows: usize, num_channels: usize) -> PatternSnapshot {
+		let empty_cell = ModCommand::new(0, 0, 0, 0, 0, 0).unwrap();
+
+		PatternSnapshot {
+			name: String::new(),
+			rows: vec![vec![empty_cell; num_channels]; num_rows],
+		}
+	}
+
+	fn set_effect(pattern: &mut PatternSnapshot, row_num: usize, channel_num: usize, command: u8, param: u8) {
+		pattern.rows[row_num][channel_num] = ModCommand::new(0, 0, 0, command, 0, param).unwrap();
+	}
+
+	#[test]
+	fn playback_follows_order_list() {
+		let data = PatternData {
+			num_channels: 2,
+			patterns: vec![empty_pattern(3, 2), empty_pattern(2, 2)],
+			orders: vec![1, ORDER_SKIP, 0],
+		};
+
+		let rows: Vec<_> = data.playback_rows().collect();
+		assert_eq!(rows, vec![(0, 1, 0), (0, 1, 1), (2, 0, 0), (2, 0, 1), (2, 0, 2)]);
+	}
+
+	#[test]
+	fn playback_stops_at_end_of_song_marker() {
+		let data = PatternData {
+			num_channels: 1,
+			patterns: vec![empty_pattern(1, 1)],
+			orders: vec![0, 0xFFFF, 0],
+		};
+
+		let rows: Vec<_> = data.playback_rows().collect();
+		assert_eq!(rows, vec![(0, 0, 0)]);
+	}
+
+	#[test]
+	fn playback_follows_jumps_and_breaks() {
+		let mut first = empty_pattern(4, 2);
+		set_effect(&mut first, 1, 1, 14, 2); // Break to row 2 of next order
+		let mut second = empty_pattern(4, 2);
+		set_effect(&mut second, 3, 0, 12, 0); // Jump back to order 0, where the song loops
+
+		let data = PatternData {
+			num_channels: 2,
+			patterns: vec![first, second],
+			orders: vec![0, 1],
+		};
+
+		let rows: Vec<_> = data.playback_rows().collect();
+		assert_eq!(rows, vec![(0, 0, 0), (0, 0, 1), (1, 1, 2), (1, 1, 3)]);
+	}
+
+	#[test]
+	fn playback_combines_jump_and_break() {
+		let mut first = empty_pattern(2, 2);
+		set_effect(&mut first, 0, 0, 12, 2); // Jump to order 2...
+		set_effect(&mut first, 0, 1, 14, 1); // ...and to row 1 on the same row
+
+		let data = PatternData {
+			num_channels: 2,
+			patterns: vec![first, empty_pattern(2, 2)],
+			orders: vec![0, 1, 1],
+		};
+
+		let rows: Vec<_> = data.playback_rows().collect();
+		assert_eq!(rows, vec![(0, 0, 0), (2, 1, 1)]);
+	}
+
+	#[test]
+	fn playback_repeats_pattern_loops() {
+		let mut pattern = empty_pattern(4, 1);
+		set_effect(&mut pattern, 1, 0, 19, 0x60); // E60 : Loop start
+		set_effect(&mut pattern, 2, 0, 19, 0x62); // E62 : Loop back twice
+
+		let data = PatternData {
+			num_channels: 1,
+			patterns: vec![pattern],
+			orders: vec![0],
+		};
+
+		let rows: Vec<u8> = data.playback_rows().map(|(_, _, row)| row as u8).collect();
+		assert_eq!(rows, vec![0, 1, 2, 1, 2, 1, 2, 3]);
+	}
+
+	#[test]
+	fn playback_repeats_s3m_pattern_loops() {
+		let mut pattern = empty_pattern(3, 2);
+		set_effect(&mut pattern, 2, 1, 20, 0xB1); // SB1 : Loop back once, to the start of the pattern
+
+		let data = PatternData {
+			num_channels: 2,
+			patterns: vec![pattern],
+			orders: vec![0],
+		};
+
+		let rows: Vec<u8> = data.playback_rows().map(|(_, _, row)| row as u8).collect();
+		assert_eq!(rows, vec![0, 1, 2, 0, 1, 2]);
+	}
+
+	#[test]
+	fn unatco_playback_rows_are_valid() {
+		let mut module = test_helper::load_file_as_module("UNATCO.it").unwrap();
+		let snapshot = module.snapshot_patterns().unwrap();
+
+		let mut num_rows = 0;
+		for (order, pattern, row) in snapshot.playback_rows() {
+			assert_eq!(snapshot.orders[order as usize], pattern);
+			assert!(snapshot.get_pattern_by_number(pattern).unwrap().get_cell(row, 0).is_some());
+			num_rows += 1;
+		}
+
+		assert!(num_rows > 0);
+	}
+
+	#[test]
+	fn out_of_range_lookups_return_none() {
+		let mut module = test_helper::load_file_as_module("empty_module.xm").unwrap();
+		let snapshot = module.snapshot_patterns().unwrap();
+
+		assert!(snapshot.get_pattern_by_number(-1).is_none());
+		assert!(snapshot.get_pattern_by_number(snapshot.patterns.len() as i32).is_none());
+		assert!(snapshot.get_pattern_by_order(-1).is_none());
+		assert!(snapshot.get_pattern_by_order(snapshot.orders.len() as i32).is_none());
+	}
+}
@@ -1,6 +1,7 @@
 use super::Module;
 use super::Logger;
 use super::ctls::Ctl;
+use super::timing::{RowTiming, TimingMap};
 use std::fs::File;
 use std::io::prelude::*;
 
@@ -9,10 +10,15 @@ pub fn load_file_as_module(file_path : &str) -> Result<Module, ()> {
 }
 
 pub fn load_file_as_module_with_ctls(file_path: &str, logger: Logger, init_ctls: &[Ctl]) -> Result<Module, ()> {
+	let buf = read_file(file_path);
+	Module::create_from_memory(&buf, logger, init_ctls)
+}
+
+pub fn read_file(file_path: &str) -> Vec<u8> {
 	let mut f = File::open(file_path).expect("unable to open file");
 	let mut buf = Vec::new();
 	f.read_to_end(&mut buf).expect("failed to read file completely");
-	Module::create_from_memory(&mut buf, logger, init_ctls)
+	buf
 }
 
 pub fn stream_file_as_module(file_path : &str) -> Result<Module, ()> {
@@ -24,3 +30,51 @@ pub fn stream_file_as_module_with_ctls(file_path: &str, logger: Logger, init_ctl
 
 	Module::create(&mut stream, logger, init_ctls)
 }
+
+/// Build a timing map at 1000 Hz, speed 6, playing each (order, row) position
+/// in turn for `row_frames` frames. Each order plays the pattern of the same number.
+pub fn build_timing_map(positions: &[(i32, i32)], row_frames: u64) -> TimingMap {
+	let rows = positions.iter().enumerate().map(|(index, &(order, row))| {
+		let frame = index as u64 * row_frames;
+		RowTiming { order, pattern: order, row, time_seconds: frame as f64 / 1000.0, frame, tempo: 125, speed: 6 }
+	}).collect();
+
+	TimingMap::new(1000, rows, positions.len() as u64 * row_frames)
+}
+
+/// Build a minimal 4-channel ProTracker module, playing a looped
+/// square wave on the first channel for one pattern.
+pub fn build_test_mod() -> Vec<u8> {
+	let mut data = Vec::new();
+
+	// Title
+	data.extend_from_slice(&[b'T'; 20]);
+
+	// Sample headers : a 64 byte looped square wave, then 30 empty samples
+	let square_wave_words = 32u16;
+	data.extend_from_slice(&[b'S'; 22]);
+	data.extend_from_slice(&[(square_wave_words >> 8) as u8, square_wave_words as u8, 0, 64, 0, 0]);
+	data.extend_from_slice(&[(square_wave_words >> 8) as u8, square_wave_words as u8]);
+
+	for _ in 1..31 {
+		data.extend_from_slice(&[0; 22]);
+		data.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+	}
+
+	// Song length, restart position, orders and format tag
+	data.extend_from_slice(&[1, 127]);
+	data.extend_from_slice(&[0; 128]);
+	data.extend_from_slice(b"M.K.");
+
+	// Pattern 0 : C-3 (period 214) with sample 1 on the first row and channel
+	let period = 214u16;
+	data.extend_from_slice(&[(period >> 8) as u8, period as u8, 0x10, 0x00]);
+	data.extend_from_slice(&[0; 64 * 4 * 4 - 4]);
+
+	// Sample data
+	for i in 0..(square_wave_words * 2) {
+		data.push(if i < square_wave_words { 0x60 } else { 0xA0 });
+	}
+
+	data
+}
@@ -0,0 +1,342 @@
+//! Definitions for the timing map, which gives the time at which
+//! every row of the song starts playing.
+//!
+//! The map is built by rendering the whole song and discarding the audio, so it
+//! accounts for everything libopenmpt does during playback : tempo and speed changes,
+//! pattern delays, loops, jumps and the current render ctls (e.g. `play.tempo_factor`).
+//!
+//! Rows are rendered in large blocks while they are known to keep playing, and in small
+//! blocks around the time they are expected to end, based on the length of the previous
+//! rows played at the same tempo and speed. Those lengths are also used to notice rows
+//! that are played again right after themselves, which do not change the position.
+
+use super::Module;
+use super::ctls::AtEndBehavior;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::os::raw::*;
+
+// Number of frames rendered between position queries around row changes,
+// which bounds the precision of the map
+const TIMING_FINE_FRAMES:usize = 32;
+// Largest number of frames rendered between position queries
+const TIMING_COARSE_FRAMES:usize = 4096;
+
+/// The timing of a single played row.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize))]
+pub struct RowTiming {
+	pub order: i32,
+	pub pattern: i32,
+	pub row: i32,
+	/// Time at which the row starts, in seconds from the start of the song
+	pub time_seconds: c_double,
+	/// Time at which the row starts, in frames from the start of the song
+	pub frame: u64,
+	/// Tempo in effect on that row, as returned by `get_current_tempo`
+	pub tempo: i32,
+	/// Ticks per row in effect on that row, as returned by `get_current_speed`
+	pub speed: i32,
+}
+
+/// The start time of every row played in the current sub-song,
+/// obtained with `Module::timing_map`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimingMap {
+	/// The sample rate used to count frames.
+	pub sample_rate: i32,
+	/// Every row played, in playback order. Rows played several times
+	/// (in pattern loops, for example) have one entry each time.
+	pub rows: Vec<RowTiming>,
+	/// The length of the song, in frames.
+	pub total_frames: u64,
+	// Indices in `rows` of every entry for an (order, row) pair
+	positions: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl TimingMap {
+	/// Build a timing map from rows that were already timed, such as the
+	/// `rows` of a map computed earlier and stored.
+	///
+	/// ### Parameters
+	/// * `sample_rate` : The sample rate used to count frames.
+	/// * `rows` : Every row played, in playback order.
+	/// * `total_frames` : The length of the song, in frames.
+	pub fn new(sample_rate: i32, rows: Vec<RowTiming>, total_frames: u64) -> TimingMap {
+		let mut positions: HashMap<(i32, i32), Vec<usize>> = HashMap::new();
+
+		for (index, timing) in rows.iter().enumerate() {
+			positions.entry((timing.order, timing.row)).or_default().push(index);
+		}
+
+		TimingMap { sample_rate, rows, total_frames, positions }
+	}
+
+	/// The length of the song, in seconds.
+	pub fn duration_seconds(&self) -> c_double {
+		self.total_frames as c_double / c_double::from(self.sample_rate)
+	}
+
+	/// Get the row playing at a given frame.
+	///
+	/// ### Returns
+	/// The row, or None if `frame` is past the end of the song or the map is empty.
+	pub fn row_at_frame(&self, frame: u64) -> Option<&RowTiming> {
+		if frame >= self.total_frames {
+			return None;
+		}
+
+		// Index of the first row starting after that frame
+		let next = self.rows.partition_point(|timing| timing.frame <= frame);
+		if next == 0 { None } else { self.rows.get(next - 1) }
+	}
+
+	/// Get the row playing at a given time.
+	///
+	/// ### Returns
+	/// The row, or None if `seconds` is negative, past the end of the song, or the map is empty.
+	pub fn row_at_seconds(&self, seconds: c_double) -> Option<&RowTiming> {
+		if seconds.is_nan() || seconds < 0.0 {
+			return None;
+		}
+
+		self.row_at_frame((seconds * c_double::from(self.sample_rate)) as u64)
+	}
+
+	// Ranges of indices in `rows` of consecutive entries for the same (order, row),
+	// which is how rows played again in place (by pattern delays or loops on a
+	// single row) show up in the map
+	pub(crate) fn row_runs(&self) -> Vec<Range<usize>> {
+		let mut runs: Vec<Range<usize>> = Vec::new();
+
+		for (index, timing) in self.rows.iter().enumerate() {
+			match runs.last_mut() {
+				Some(run) if (self.rows[run.start].order, self.rows[run.start].row) == (timing.order, timing.row) => run.end = index + 1,
+				_ => runs.push(index..index + 1),
+			}
+		}
+
+		runs
+	}
+
+	// Frame at which an entry of `rows` starts, or the end of the song past the last one
+	pub(crate) fn entry_frame(&self, index: usize) -> u64 {
+		self.rows.get(index).map_or(self.total_frames, |timing| timing.frame)
+	}
+
+	/// Get every time a row is played.
+	///
+	/// ### Parameters
+	/// * `order` : The order position of the row.
+	/// * `row` : The row number in the pattern at that order.
+	///
+	/// ### Returns
+	/// The timing of every occurrence of the row, in playback order.
+	/// Empty if the row is never played.
+	pub fn occurrences(&self, order: i32, row: i32) -> Vec<&RowTiming> {
+		self.positions.get(&(order, row))
+			.map(|indices| indices.iter().map(|&index| &self.rows[index]).collect())
+			.unwrap_or_default()
+	}
+
+	/// Get the first time a row is played.
+	///
+	/// ### Returns
+	/// The timing of that row, or None if the row is never played.
+	pub fn first_occurrence(&self, order: i32, row: i32) -> Option<&RowTiming> {
+		self.positions.get(&(order, row))
+			.and_then(|indices| indices.first())
+			.map(|&index| &self.rows[index])
+	}
+}
+
+impl Module {
+	/// Compute the start time of every row played in the current sub-song.
+	///
+	/// ### Parameters
+	/// * `sample_rate` : The sample rate used to count frames.
+	///
+	/// ### Returns
+	/// The timing map of the song, played once from its start.
+	///
+	/// ### Remarks
+	/// This renders the whole song, which can take a while for long modules.
+	/// Row start times are accurate to within 32 frames, unless the tempo
+	/// speeds up in the middle of the previous row.
+	///
+	/// A row played several times in a row (a pattern loop or delay on a single row)
+	/// gets one entry per repetition, provided that another row with the same
+	/// tempo and speed was played before it.
+	///
+	/// The repeat count, the `play.at_end` ctl and the playback position are restored
+	/// afterwards, the latter only approximately (see `set_position_seconds`).
+	pub fn timing_map(&mut self, sample_rate: i32) -> TimingMap {
+		let saved_position = self.get_position_seconds();
+		let saved_repeat_count = self.get_repeat_count();
+		let saved_at_end = self.ctl_get_play_at_end();
+
+		self.set_repeat_count(0);
+		if saved_at_end.is_some() {
+			// Keep fadeouts and restarts out of the map
+			let _ = self.ctl_set_play_at_end(AtEndBehavior::Stop);
+		}
+		self.set_position_seconds(0.0);
+
+		let mut buffer = [0.0 as c_float; TIMING_COARSE_FRAMES];
+		let mut rows: Vec<RowTiming> = Vec::new();
+		let mut total_frames = 0u64;
+		let mut block_frames = 0u64;
+		// Shortest length of the rows played so far, by (tempo, speed)
+		let mut row_frames: HashMap<(i32, i32), u64> = HashMap::new();
+
+		loop {
+			let timing = RowTiming {
+				order: self.get_current_order(),
+				pattern: self.get_current_pattern(),
+				row: self.get_current_row(),
+				time_seconds: 0.0,
+				frame: 0,
+				tempo: self.get_current_tempo(),
+				speed: self.get_current_speed(),
+			};
+
+			let expected_frames = match rows.last() {
+				Some(last) if (last.order, last.row) == (timing.order, timing.row) => {
+					let expected_frames = row_frames.get(&(last.tempo, last.speed)).cloned();
+
+					// Still on the same row after a whole row length, it was played again
+					match expected_frames {
+						Some(length) if (last.tempo, last.speed) == (timing.tempo, timing.speed)
+							&& total_frames >= last.frame + length + TIMING_FINE_FRAMES as u64 =>
+						{
+							rows.push(RowTiming { frame: last.frame + length, ..timing });
+						},
+						_ => {},
+					}
+
+					expected_frames
+				},
+				last => {
+					// The row started somewhere in the previous block, place it at its start
+					let frame = total_frames - block_frames;
+
+					// Rows during which the tempo changed are not representative
+					if let Some(last) = last.filter(|last| (last.tempo, last.speed) == (timing.tempo, timing.speed)) {
+						let length = row_frames.entry((last.tempo, last.speed)).or_insert(u64::MAX);
+						*length = (*length).min(frame - last.frame);
+					}
+
+					rows.push(RowTiming { frame, ..timing });
+					row_frames.get(&(timing.tempo, timing.speed)).cloned()
+				},
+			};
+
+			// Render up to halfway to the expected end of the row, finely once close to it
+			let row_start = rows.last().map_or(0, |last| last.frame);
+			let remaining = expected_frames.map_or(0, |length| (row_start + length).saturating_sub(total_frames));
+			let block_size = (remaining as usize / 2).clamp(TIMING_FINE_FRAMES, TIMING_COARSE_FRAMES);
+
+			let frames = self.read_float_mono(sample_rate, &mut buffer[..block_size]);
+			if frames == 0 {
+				break;
+			}
+
+			block_frames = frames as u64;
+			total_frames += block_frames;
+		}
+
+		for timing in &mut rows {
+			timing.time_seconds = timing.frame as c_double / c_double::from(sample_rate);
+		}
+
+		self.set_repeat_count(saved_repeat_count);
+		if let Some(at_end) = saved_at_end {
+			let _ = self.ctl_set_play_at_end(at_end);
+		}
+		self.set_position_seconds(saved_position);
+
+		TimingMap::new(sample_rate, rows, total_frames)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::Logger;
+	use super::super::test_helper;
+
+	fn test_map() -> TimingMap {
+		test_helper::build_timing_map(&[(0, 0), (0, 1), (0, 0), (0, 1), (1, 0)], 100)
+	}
+
+	#[test]
+	fn rows_are_found_by_time() {
+		let map = test_map();
+
+		assert_eq!(map.duration_seconds(), 0.5);
+		assert_eq!(map.row_at_frame(0).unwrap().row, 0);
+		assert_eq!(map.row_at_frame(150).unwrap().frame, 100);
+		assert_eq!(map.row_at_frame(499).unwrap().order, 1);
+		assert_eq!(map.row_at_frame(500), None);
+		assert_eq!(map.row_at_seconds(0.25).unwrap().frame, 200);
+		assert_eq!(map.row_at_seconds(-1.0), None);
+	}
+
+	#[test]
+	fn rows_are_found_by_position() {
+		let map = test_map();
+
+		let frames: Vec<u64> = map.occurrences(0, 1).iter().map(|timing| timing.frame).collect();
+		assert_eq!(frames, vec![100, 300]);
+		assert_eq!(map.first_occurrence(1, 0).unwrap().frame, 400);
+		assert!(map.occurrences(5, 0).is_empty());
+		assert_eq!(map.first_occurrence(5, 0), None);
+	}
+
+	#[test]
+	fn rows_played_in_place_are_grouped() {
+		let map = test_helper::build_timing_map(&[(0, 0), (0, 1), (0, 1), (0, 1), (0, 0), (1, 0), (1, 0)], 100);
+
+		assert_eq!(map.row_runs(), vec![0..1, 1..4, 4..5, 5..7]);
+		assert_eq!(map.entry_frame(4), 400);
+		assert_eq!(map.entry_frame(7), 700);
+	}
+
+	#[test]
+	fn repeated_rows_are_split() {
+		// Pattern delay (EE2) on the second row, playing it 3 times
+		let mut data = test_helper::build_test_mod();
+		let cell = 1084 + 16 + 4;
+		data[cell..cell + 4].copy_from_slice(&[0x00, 0x00, 0x0E, 0xE2]);
+
+		let mut module = Module::create_from_memory(&data, Logger::None, &[]).unwrap();
+		let map = module.timing_map(48000);
+
+		// 6 ticks at 125 BPM
+		let row_frames = 5760;
+		let positions: Vec<(i32, u64)> = map.rows[..5].iter().map(|timing| (timing.row, timing.frame)).collect();
+		let expected = [(0, 0), (1, row_frames), (1, 2 * row_frames), (1, 3 * row_frames), (2, 4 * row_frames)];
+
+		for (&(row, frame), &(expected_row, expected_frame)) in positions.iter().zip(expected.iter()) {
+			assert_eq!(row, expected_row);
+			assert!((frame as i64 - expected_frame as i64).abs() <= 32, "{:?}", positions);
+		}
+
+		assert_eq!(map.rows.len(), 66);
+	}
+
+	#[test]
+	fn timing_map_matches_duration() {
+		let mut module = test_helper::load_file_as_module("UNATCO.it").unwrap();
+		let duration = module.get_duration_seconds();
+		let map = module.timing_map(48000);
+
+		assert!((map.duration_seconds() - duration).abs() < 0.1);
+		assert_eq!((map.rows[0].order, map.rows[0].row, map.rows[0].frame), (0, 0, 0));
+		assert!(map.rows.windows(2).all(|pair| pair[0].frame <= pair[1].frame));
+
+		let last = map.rows.last().unwrap();
+		assert_eq!(map.row_at_frame(map.total_frames - 1), Some(last));
+		assert_eq!(module.get_position_seconds(), 0.0);
+	}
+}
@@ -0,0 +1,266 @@
+//! Functions and types used to quickly find out whether some data
+//! could be a module file, by only looking at its header.
+//!
+//! Unlike `module::could_open_propability`, this only needs the first few bytes
+//! of a file, and gives a definitive answer as soon as it has enough of them.
+
+use error::Error;
+use ffi;
+use openmpt_sys;
+use std::ops::BitOr;
+use std::os::raw::*;
+use std::ptr;
+
+/// Which kinds of formats to look for when probing a header.
+///
+/// Flags can be combined with `|`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ProbeFlags(u64);
+
+impl ProbeFlags {
+	/// Look for nothing, every probe fails.
+	pub const NONE: ProbeFlags = ProbeFlags(0x0);
+	/// Look for module formats.
+	pub const MODULES: ProbeFlags = ProbeFlags(0x1);
+	/// Look for container formats (e.g. umx).
+	pub const CONTAINERS: ProbeFlags = ProbeFlags(0x2);
+	/// Look for both module and container formats.
+	pub const DEFAULT: ProbeFlags = ProbeFlags(0x1 | 0x2);
+
+	/// Get the raw flag value passed to libopenmpt.
+	pub fn bits(&self) -> u64 {
+		self.0
+	}
+}
+
+impl Default for ProbeFlags {
+	fn default() -> ProbeFlags {
+		ProbeFlags::DEFAULT
+	}
+}
+
+impl BitOr for ProbeFlags {
+	type Output = ProbeFlags;
+
+	fn bitor(self, rhs: ProbeFlags) -> ProbeFlags {
+		ProbeFlags(self.0 | rhs.0)
+	}
+}
+
+/// The outcome of probing a file header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProbeResult {
+	/// The data looks like a supported format, loading it will most likely succeed.
+	Success,
+	/// The data is definitely not a supported format.
+	Failure,
+	/// More data is needed to decide.
+	///
+	/// Contains the number of additional bytes that should be fetched
+	/// before probing again.
+	WantMoreData(usize),
+}
+
+/// Get the header size recommended for probing.
+///
+/// ### Returns
+/// The number of bytes that is very likely to be enough for
+/// `probe_header` to give a definitive answer.
+pub fn get_recommended_size() -> usize {
+	unsafe {
+		ffi::openmpt_probe_file_header_get_recommended_size()
+	}
+}
+
+/// Probe the start of a file, to find out whether it could be a module.
+///
+/// ### Parameters
+/// * `header` : The first bytes of the file, ideally `get_recommended_size()` of them.
+/// * `flags` : The kinds of formats to look for.
+///
+/// ### Returns
+/// Whether the file is supported, or how many more bytes are needed to tell.
+///
+/// ### Remarks
+/// Some formats can only be identified with the total file size in hand,
+/// use `probe_header_with_file_size` when it is known.
+pub fn probe_header(header: &[u8], flags: ProbeFlags) -> Result<ProbeResult, Error> {
+	let return_code = unsafe {
+		ffi::openmpt_probe_file_header_without_filesize(
+			flags.bits(),
+			header.as_ptr() as *const _,
+			header.len(),
+			Some(openmpt_sys::openmpt_log_func_silent),
+			ptr::null_mut(), // loguser
+			None, // errfunc
+			ptr::null_mut(), // erruser
+			ptr::null_mut(), // error
+			ptr::null_mut(), // error_message
+		)
+	};
+
+	to_probe_result(return_code, header.len())
+}
+
+/// Probe the start of a file of a known size, to find out whether it could be a module.
+///
+/// ### Parameters
+/// * `header` : The first bytes of the file, ideally `get_recommended_size()` of them.
+/// * `flags` : The kinds of formats to look for.
+/// * `file_size` : The size of the whole file, in bytes.
+///
+/// ### Returns
+/// Whether the file is supported, or how many more bytes are needed to tell.
+pub fn probe_header_with_file_size(header: &[u8], flags: ProbeFlags, file_size: u64) -> Result<ProbeResult, Error> {
+	let return_code = unsafe {
+		ffi::openmpt_probe_file_header(
+			flags.bits(),
+			header.as_ptr() as *const _,
+			header.len(),
+			file_size,
+			Some(openmpt_sys::openmpt_log_func_silent),
+			ptr::null_mut(), // loguser
+			None, // errfunc
+			ptr::null_mut(), // erruser
+			ptr::null_mut(), // error
+			ptr::null_mut(), // error_message
+		)
+	};
+
+	to_probe_result(return_code, header.len())
+}
+
+/// Incremental header prober, for data that arrives in chunks (e.g. uploads).
+///
+/// Feed it chunks until it returns something other than `ProbeResult::WantMoreData`,
+/// which lets callers reject non-module files without waiting for the whole file.
+#[derive(Debug, Clone)]
+pub struct HeaderProber {
+	flags: ProbeFlags,
+	file_size: Option<u64>,
+	header: Vec<u8>,
+	result: Option<ProbeResult>,
+}
+
+impl HeaderProber {
+	/// Create a prober looking for the given kinds of formats.
+	///
+	/// ### Parameters
+	/// * `flags` : The kinds of formats to look for.
+	/// * `file_size` : The size of the whole file in bytes, if known.
+	pub fn new(flags: ProbeFlags, file_size: Option<u64>) -> HeaderProber {
+		HeaderProber {
+			flags,
+			file_size,
+			header: Vec::new(),
+			result: None,
+		}
+	}
+
+	/// Add the next chunk of the file, and probe again.
+	///
+	/// ### Returns
+	/// The result of probing everything fed so far. Once a definitive
+	/// answer has been given, further chunks are ignored.
+	pub fn feed(&mut self, chunk: &[u8]) -> Result<ProbeResult, Error> {
+		if let Some(result) = self.result {
+			return Ok(result);
+		}
+
+		self.header.extend_from_slice(chunk);
+
+		let result = match self.file_size {
+			Some(file_size) => probe_header_with_file_size(&self.header, self.flags, file_size)?,
+			None => probe_header(&self.header, self.flags)?,
+		};
+
+		match result {
+			ProbeResult::WantMoreData(_) => {},
+			_ => self.result = Some(result),
+		}
+
+		Ok(result)
+	}
+
+	/// Get the number of bytes fed so far.
+	pub fn len(&self) -> usize {
+		self.header.len()
+	}
+
+	/// Whether nothing has been fed yet.
+	pub fn is_empty(&self) -> bool {
+		self.header.is_empty()
+	}
+}
+
+fn to_probe_result(return_code: c_int, header_len: usize) -> Result<ProbeResult, Error> {
+	match return_code {
+		ffi::PROBE_FILE_HEADER_RESULT_SUCCESS => Ok(ProbeResult::Success),
+		ffi::PROBE_FILE_HEADER_RESULT_FAILURE => Ok(ProbeResult::Failure),
+		ffi::PROBE_FILE_HEADER_RESULT_WANTMOREDATA => {
+			let recommended_size = get_recommended_size();
+			Ok(ProbeResult::WantMoreData(missing_bytes(recommended_size, header_len)))
+		},
+		_ => Err(Error::OperationFailed("probe file header")),
+	}
+}
+
+// Never ask for 0 bytes, libopenmpt may need more than the recommended size
+fn missing_bytes(recommended_size: usize, header_len: usize) -> usize {
+	if header_len < recommended_size {
+		recommended_size - header_len
+	} else {
+		recommended_size
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use module::test_helper::read_file;
+
+	#[test]
+	fn flags_can_be_combined() {
+		assert_eq!(ProbeFlags::MODULES | ProbeFlags::CONTAINERS, ProbeFlags::DEFAULT);
+		assert_eq!(ProbeFlags::default().bits(), 0x3);
+		assert_eq!((ProbeFlags::NONE | ProbeFlags::MODULES).bits(), 0x1);
+	}
+
+	#[test]
+	fn missing_bytes_are_never_zero() {
+		assert_eq!(missing_bytes(2048, 100), 1948);
+		assert_eq!(missing_bytes(2048, 0), 2048);
+		assert_eq!(missing_bytes(2048, 2048), 2048);
+	}
+
+	#[test]
+	fn module_headers_are_recognized() {
+		let xm = read_file("empty_module.xm");
+		let it = read_file("UNATCO.it");
+
+		assert_eq!(probe_header(&xm, ProbeFlags::DEFAULT).unwrap(), ProbeResult::Success);
+		assert_eq!(probe_header_with_file_size(&it[..get_recommended_size().min(it.len())], ProbeFlags::MODULES, it.len() as u64).unwrap(), ProbeResult::Success);
+		assert_eq!(probe_header(&xm, ProbeFlags::NONE).unwrap(), ProbeResult::Failure);
+	}
+
+	#[test]
+	fn non_module_headers_are_rejected() {
+		let text = vec![b'#'; 4096];
+		assert_eq!(probe_header_with_file_size(&text, ProbeFlags::DEFAULT, text.len() as u64).unwrap(), ProbeResult::Failure);
+	}
+
+	#[test]
+	fn incremental_probing_asks_for_more_data() {
+		let xm = read_file("empty_module.xm");
+		let mut prober = HeaderProber::new(ProbeFlags::DEFAULT, Some(xm.len() as u64));
+
+		match prober.feed(&xm[..4]).unwrap() {
+			ProbeResult::WantMoreData(more) => assert!(more > 0),
+			result => panic!("Probing 4 bytes should not be conclusive, got {:?}", result),
+		}
+
+		assert_eq!(prober.feed(&xm[4..]).unwrap(), ProbeResult::Success);
+		assert_eq!(prober.feed(b"garbage").unwrap(), ProbeResult::Success);
+		assert_eq!(prober.len(), xm.len());
+	}
+}
@@ -0,0 +1,346 @@
+//! Scanning directories for module files, to catalog which ones can be played.
+//!
+//! Files are classified in steps, each one more expensive than the last :
+//! the file extension is checked first, then `could_open_propability` is used
+//! with the configured effort, and finally the module is loaded (without its
+//! samples or plugins) to read its metadata and duration.
+//!
+//! Files are processed in parallel, each worker thread using its own `Module` instances.
+
+use info;
+use module::{self, Module, Logger, CouldOpenEffort};
+use module::ctls::Ctl;
+use module::metadata::ModuleMetadata;
+use std::fs::{self, File};
+use std::io::{self, Seek, SeekFrom};
+use std::os::raw::*;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::vec;
+
+/// How a scanned file was classified.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileClass {
+	/// The file or directory could not be read.
+	Unreadable,
+	/// libopenmpt does not support the file extension, the file was not opened.
+	UnsupportedExtension,
+	/// `could_open_propability` did not exceed the minimum probability.
+	Unlikely,
+	/// The file passed the probability check, but was not loaded.
+	Probable,
+	/// The file passed the probability check, but could not be loaded.
+	LoadFailed,
+	/// The file was loaded successfully.
+	Playable,
+}
+
+/// The catalog entry for a single scanned file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CatalogEntry {
+	pub path: PathBuf,
+	/// Size of the file in bytes, 0 if unreadable
+	pub size: u64,
+	pub class: FileClass,
+	/// Result of `could_open_propability`, if it was called
+	pub probability: Option<f64>,
+	/// Metadata of the module, if it was loaded
+	pub metadata: Option<ModuleMetadata>,
+	/// Duration of the default sub-song, if the module was loaded
+	pub duration_seconds: Option<c_double>,
+	/// What went wrong, for unreadable files and failed loads
+	pub error: Option<String>,
+}
+
+impl CatalogEntry {
+	fn new(path: PathBuf, class: FileClass) -> CatalogEntry {
+		CatalogEntry {
+			path,
+			size: 0,
+			class,
+			probability: None,
+			metadata: None,
+			duration_seconds: None,
+			error: None,
+		}
+	}
+
+	fn unreadable(path: PathBuf, error: &io::Error) -> CatalogEntry {
+		let mut entry = CatalogEntry::new(path, FileClass::Unreadable);
+		entry.error = Some(error.to_string());
+		entry
+	}
+}
+
+/// Settings for `scan_directory`.
+#[derive(Debug, Clone)]
+pub struct ScanOptions {
+	/// Effort to make when calling `could_open_propability`
+	pub effort: CouldOpenEffort,
+	/// Files with a probability at or below this are classified as `Unlikely`
+	pub min_probability: f64,
+	/// Skip files whose extension is not supported, without opening them.
+	///
+	/// Amiga-style prefixes (e.g. `mod.title`) are also accepted.
+	pub check_extensions: bool,
+	/// Load probable files to read their metadata and duration
+	pub load_metadata: bool,
+	/// Scan subdirectories as well
+	pub recursive: bool,
+	/// Number of worker threads, at least 1
+	pub threads: usize,
+}
+
+impl Default for ScanOptions {
+	fn default() -> ScanOptions {
+		ScanOptions {
+			effort: CouldOpenEffort::VerifyHeader,
+			min_probability: 0.0,
+			check_extensions: true,
+			load_metadata: true,
+			recursive: true,
+			threads: thread::available_parallelism().map(|threads| threads.get()).unwrap_or(4),
+		}
+	}
+}
+
+/// Scan a directory and catalog every file in it.
+///
+/// ### Parameters
+/// * `dir` : The directory to scan.
+/// * `options` : How to scan and classify files.
+///
+/// ### Returns
+/// One entry per file, sorted by path, or an error if `dir` cannot be read.
+/// Errors in subdirectories and files are reported in their entries.
+pub fn scan_directory(dir: &Path, options: &ScanOptions) -> io::Result<Vec<CatalogEntry>> {
+	let mut entries = Vec::new();
+	scan_directory_with(dir, options, |entry| entries.push(entry))?;
+
+	entries.sort_by(|a, b| a.path.cmp(&b.path));
+	Ok(entries)
+}
+
+/// Scan a directory, and pass each catalog entry to a callback as soon as it is ready.
+///
+/// ### Parameters
+/// * `dir` : The directory to scan.
+/// * `options` : How to scan and classify files.
+/// * `on_entry` : Called on the current thread for every entry, in no particular order.
+///
+/// ### Returns
+/// The number of entries produced, or an error if `dir` cannot be read.
+pub fn scan_directory_with<F: FnMut(CatalogEntry)>(dir: &Path, options: &ScanOptions, mut on_entry: F) -> io::Result<usize> {
+	let mut files = Vec::new();
+	let mut unreadable = Vec::new();
+	list_files(dir, options.recursive, &mut files, &mut unreadable)?;
+	files.sort();
+
+	let entry_count = files.len() + unreadable.len();
+	for entry in unreadable {
+		on_entry(entry);
+	}
+
+	let queue: Arc<Mutex<vec::IntoIter<PathBuf>>> = Arc::new(Mutex::new(files.into_iter()));
+	let (sender, receiver) = mpsc::channel();
+
+	let workers: Vec<_> = (0..options.threads.max(1)).map(|_| {
+		let queue = Arc::clone(&queue);
+		let sender = sender.clone();
+		let options = options.clone();
+
+		thread::spawn(move || {
+			loop {
+				// Release the lock before cataloging the file
+				let next_path = queue.lock().unwrap().next();
+				let path = match next_path {
+					Some(path) => path,
+					None => break,
+				};
+
+				if sender.send(catalog_file(&path, &options)).is_err() {
+					break;
+				}
+			}
+		})
+	}).collect();
+
+	drop(sender);
+	for entry in receiver {
+		on_entry(entry);
+	}
+
+	for worker in workers {
+		worker.join().expect("Scanner worker thread panicked");
+	}
+
+	Ok(entry_count)
+}
+
+/// Classify a single file.
+///
+/// ### Parameters
+/// * `path` : The file to classify.
+/// * `options` : How to classify the file. `recursive` and `threads` are ignored.
+pub fn catalog_file(path: &Path, options: &ScanOptions) -> CatalogEntry {
+	let size = match fs::metadata(path) {
+		Ok(file_info) => file_info.len(),
+		Err(error) => return CatalogEntry::unreadable(path.to_owned(), &error),
+	};
+
+	let mut entry = CatalogEntry::new(path.to_owned(), FileClass::UnsupportedExtension);
+	entry.size = size;
+
+	if options.check_extensions && !has_supported_extension(path) {
+		return entry;
+	}
+
+	let mut file = match File::open(path) {
+		Ok(file) => file,
+		Err(error) => return CatalogEntry { size, ..CatalogEntry::unreadable(path.to_owned(), &error) },
+	};
+
+	let probability = module::could_open_propability(&mut file, options.effort, Logger::None);
+	entry.probability = Some(probability);
+
+	if probability <= options.min_probability {
+		entry.class = FileClass::Unlikely;
+		return entry;
+	}
+
+	if !options.load_metadata {
+		entry.class = FileClass::Probable;
+		return entry;
+	}
+
+	if let Err(error) = file.seek(SeekFrom::Start(0)) {
+		return CatalogEntry { size, probability: Some(probability), ..CatalogEntry::unreadable(path.to_owned(), &error) };
+	}
+
+	// Patterns are needed to compute the duration
+	let load_ctls = [
+		Ctl::SkipLoadingSamples(true),
+		Ctl::SkipLoadingPlugins(true),
+	];
+
+	match Module::create(&mut file, Logger::None, &load_ctls) {
+		Ok(mut module) => {
+			entry.class = FileClass::Playable;
+			entry.metadata = Some(module.metadata());
+			entry.duration_seconds = Some(module.get_duration_seconds());
+		},
+		Err(()) => {
+			entry.class = FileClass::LoadFailed;
+			entry.error = Some("libopenmpt could not load the module".to_owned());
+		},
+	}
+
+	entry
+}
+
+// Check both the extension and the Amiga-style prefix (mod.title)
+fn has_supported_extension(path: &Path) -> bool {
+	let file_name = match path.file_name().and_then(|name| name.to_str()) {
+		Some(file_name) => file_name,
+		None => return false,
+	};
+
+	let extension = file_name.rsplit('.').next();
+	let prefix = file_name.split('.').next();
+
+	file_name.contains('.') && [extension, prefix].iter()
+		.filter_map(|&part| part)
+		.any(|part| !part.is_empty() && info::is_extension_supported(part))
+}
+
+fn list_files(dir: &Path, recursive: bool, files: &mut Vec<PathBuf>, unreadable: &mut Vec<CatalogEntry>) -> io::Result<()> {
+	for dir_entry in fs::read_dir(dir)? {
+		let dir_entry = match dir_entry {
+			Ok(dir_entry) => dir_entry,
+			// The entry has no path of its own, report the directory it is in
+			Err(error) => {
+				unreadable.push(CatalogEntry::unreadable(dir.to_owned(), &error));
+				continue;
+			},
+		};
+		let path = dir_entry.path();
+
+		match dir_entry.file_type() {
+			Ok(ref file_type) if file_type.is_dir() => {
+				if recursive {
+					if let Err(error) = list_files(&path, recursive, files, unreadable) {
+						unreadable.push(CatalogEntry::unreadable(path, &error));
+					}
+				}
+			},
+			Ok(_) => files.push(path),
+			Err(error) => unreadable.push(CatalogEntry::unreadable(path, &error)),
+		}
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use module::test_helper;
+	use std::env;
+
+	fn scan_options() -> ScanOptions {
+		ScanOptions { threads: 2, ..ScanOptions::default() }
+	}
+
+	fn make_test_dir(name: &str) -> PathBuf {
+		let dir = env::temp_dir().join(format!("openmpt-rs-scan-{}-{}", name, ::std::process::id()));
+		let _ = fs::remove_dir_all(&dir);
+		fs::create_dir_all(dir.join("sub")).unwrap();
+
+		fs::copy("empty_module.xm", dir.join("empty_module.xm")).unwrap();
+		fs::copy("UNATCO.it", dir.join("sub").join("UNATCO.it")).unwrap();
+		fs::write(dir.join("readme.txt"), "Not a module").unwrap();
+		fs::write(dir.join("fake.xm"), "Not a module either").unwrap();
+
+		dir
+	}
+
+	#[test]
+	fn missing_directory_is_an_error() {
+		assert!(scan_directory(Path::new("does/not/exist"), &scan_options()).is_err());
+	}
+
+	#[test]
+	fn files_are_classified() {
+		let dir = make_test_dir("classify");
+		let entries = scan_directory(&dir, &scan_options()).unwrap();
+		let class_of = |path: PathBuf| entries.iter().find(|entry| entry.path == path).unwrap().class;
+
+		assert_eq!(entries.len(), 4);
+		assert_eq!(class_of(dir.join("empty_module.xm")), FileClass::Playable);
+		assert_eq!(class_of(dir.join("sub").join("UNATCO.it")), FileClass::Playable);
+		assert_eq!(class_of(dir.join("readme.txt")), FileClass::UnsupportedExtension);
+		assert_eq!(class_of(dir.join("fake.xm")), FileClass::Unlikely);
+
+		let xm = entries.iter().find(|entry| entry.path == dir.join("empty_module.xm")).unwrap();
+		assert_eq!(xm.metadata.as_ref().unwrap().type_ext, "xm");
+		let duration = test_helper::load_file_as_module("empty_module.xm").unwrap().get_duration_seconds();
+		assert!(duration > 0.0);
+		assert_eq!(xm.duration_seconds, Some(duration));
+
+		fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	fn scan_options_are_respected() {
+		let dir = make_test_dir("options");
+		let options = ScanOptions { recursive: false, load_metadata: false, check_extensions: false, ..scan_options() };
+		let entries = scan_directory(&dir, &options).unwrap();
+
+		assert_eq!(entries.len(), 3);
+		assert!(entries.iter().all(|entry| entry.metadata.is_none() && entry.probability.is_some()));
+		assert_eq!(entries.iter().filter(|entry| entry.class == FileClass::Probable).count(), 1);
+
+		fs::remove_dir_all(&dir).unwrap();
+	}
+}
@@ -6,13 +6,18 @@ extern crate hound;
 use std::fs::File;
 use openmpt::module::Module;
 use openmpt::module::Logger;
+use openmpt::module::ctls::AtEndBehavior;
+use openmpt::module::render::ReadStatus;
 
 //#[test]
 fn render_unatco() {
-	render_file_to_wav("UNATCO.it");
+	let end_behavior = render_file_to_wav("UNATCO.it");
+	assert_eq!(end_behavior, AtEndBehavior::Fadeout);
 }
 
-fn render_file_to_wav(file_path : &str) {
+/// Render the whole file to `<file_path>.wav`, and return the
+/// end of song behavior that stopped rendering.
+fn render_file_to_wav(file_path : &str) -> AtEndBehavior {
 	let mut stream = File::open(file_path).expect("unable to open file");
 
 	let mut module = Module::create(&mut stream, Logger::None, &[]).unwrap();
@@ -30,9 +35,12 @@ fn render_file_to_wav(file_path : &str) {
 	let mut buffer = vec![0f32; 44100]; // 1 second at a time
 
 	loop {
-		let avail_samples = module.read_interleaved_float_stereo(
-				44100, &mut buffer) << 1; // We're in interleaved stereo
-		if avail_samples <= 0 { break; }
+		let avail_frames = module.read_interleaved_float_stereo(44100, &mut buffer);
+		let avail_samples = match module.read_status(avail_frames, buffer.len() >> 1).unwrap() {
+			ReadStatus::Rendered(frames) => frames << 1, // We're in interleaved stereo
+			ReadStatus::Ended(end_behavior) => return end_behavior,
+			ReadStatus::EmptyBuffer => unreachable!(),
+		};
 
 		for sample in &buffer[..avail_samples] {
 			writer.write_sample(*sample);